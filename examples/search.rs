@@ -2,9 +2,11 @@ use anyhow::Result;
 use chrono::{DateTime, Datelike, NaiveDateTime, Utc};
 use serde::{Deserialize, Serialize};
 use serde_json::{from_str, to_string, Value};
+use std::cell::RefCell;
 use std::fmt::{format, Debug};
 use std::fs::File;
 use std::io::{BufRead, BufReader};
+use std::rc::Rc;
 use xapian::{Database, WritableDatabase};
 
 fn main() -> Result<()> {
@@ -17,8 +19,8 @@ fn main() -> Result<()> {
     let _ = std::fs::create_dir_all("./data");
     let mut db = Database::new_with_path("./data/xapian-movie", 0).expect("Error opening database");
 
-    // let doc_count = db.get_doccount().expect("Error getting doc count");
-    // println!("doc count: {}", doc_count);
+    let doc_count = db.get_doccount().expect("Error getting doc count");
+    println!("doc count: {}", doc_count);
 
     let start_time = std::time::Instant::now();
 
@@ -29,11 +31,8 @@ fn main() -> Result<()> {
     qp.add_prefix("title", "T");
     qp.add_prefix("overview", "O");
 
-    // let mut nrp_year = xapian::RangeProcessor::new(2, "year:", xapian::RangeProcessorFlags::RP_PREFIX).expect("Error creating number range processor");
-    // qp.add_rangeprocessor(&mut nrp_year);
-
-    let mut nrp_year = xapian::NumberRangeProcessor::new(0, "year:", xapian::constants::RangeProcessorFlags::RP_PREFIX).expect("Error creating number range processor");
-    qp.add_number_rangeprocessor(&mut nrp_year);
+    let nrp_year = xapian::NumberRangeProcessor::new(0, "year:", xapian::constants::RangeProcessorFlags::RP_PREFIX).expect("Error creating number range processor");
+    qp.add_rangeprocessor(nrp_year, None).expect("Error adding range processor");
 
     qp.add_boolean_prefix("id", "Q");
 
@@ -49,8 +48,8 @@ fn main() -> Result<()> {
     let mut enquire = db.new_enquire().expect("Error creating enquire");
     enquire.set_query(&mut query).expect("set_query failed");
 
-    let mut vcspy = xapian::ValueCountMatchSpy::new(2).expect("Error creating value count match spy");
-    enquire.add_matchspy_value_count(&mut vcspy).expect("Error adding matchspy");
+    let vcspy = Rc::new(RefCell::new(xapian::ValueCountMatchSpy::new(2).expect("Error creating value count match spy")));
+    enquire.add_matchspy_value_count(&vcspy).expect("Error adding matchspy");
 
     enquire.set_sort_by_value(0, true).expect("Error setting sort by value year desc");
 
@@ -60,14 +59,9 @@ fn main() -> Result<()> {
 
     let mut stem = xapian::Stem::new("en").expect("Error creating stemmer");
     let snippet_flags = xapian::constants::SnippetFlags::SNIPPET_BACKGROUND_MODEL as i32 | xapian::constants::SnippetFlags::SNIPPET_EXHAUSTIVE as i32;
-    let mut it = mset.begin().unwrap();
-    let mut idx = 0;
-    loop {
-        if it.eq(&mut mset.end().unwrap()).unwrap() {
-            break;
-        }
-        // undefined reference to `Xapian::MSet::get_doc_by_index(unsigned int)'
-        let mut doc = it.get_document().expect("Error getting document");
+    for m in mset.iter() {
+        let mut m = m.expect("Error reading match");
+        let mut doc = m.get_document().expect("Error getting document");
         let data = doc.get_data().unwrap();
         // println!("raw doc data: {}", &data);
         let movie: Movie = from_str(&data).expect("Error parsing json");
@@ -79,11 +73,10 @@ fn main() -> Result<()> {
             "snippet: {:?}",
             mset.snippet(movie.overview.as_str(), 100, &mut stem, snippet_flags, "<b>", "</b>", "...")
         );
-        println!("{} movie: {:?}", idx, movie);
-        it.next();
-        idx += 1;
+        println!("{} docid: {} weight: {} ({}%) movie: {:?}", m.rank, m.docid, m.weight, m.percent, movie);
     }
 
+    let mut vcspy = vcspy.borrow_mut();
     println!("spy total: {}", vcspy.get_total());
 
     let mut spy = vcspy.values_begin().unwrap();
@@ -91,10 +84,10 @@ fn main() -> Result<()> {
         if spy.eq(&mut vcspy.values_end().unwrap()) {
             break;
         }
-        let value = spy.get_termfreq_value();
-        let count = spy.get_termfreq_freq();
+        let value = spy.get_termfreq_value().unwrap();
+        let count = spy.get_termfreq_freq().unwrap();
         println!("{}: {}", value, count);
-        spy.next();
+        spy.next().unwrap();
     }
 
     println!("qs={}", &qs);
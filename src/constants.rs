@@ -57,11 +57,12 @@ pub const DB_CREATE: i32 = 0x02;
  */
 pub const DB_OPEN: i32 = 0x03;
 
-/// Enum of possible query operations
-/// #[repr(i32)]
+/// Enum of possible query operations, the discriminants match Xapian::Query::op
+#[repr(i32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum XapianOp {
     /// Return iff both subqueries are satisfied
-    OpAnd,
+    OpAnd = 0,
 
     /// Return if either subquery is satisfied
     OpOr,
@@ -178,6 +179,114 @@ pub enum XapianOp {
         *  Identical to OP_OR, except for the weightings returned.
      */
     OpSynonym,
+
+    /** Pick the maximum weight of any subquery.
+        *
+        *  Matches the same documents as @a OP_OR, but the weight
+        *  contributed is the maximum weight from any matching subquery
+        *  (for OP_OR, it's the sum of the weights from the matching
+        *  subqueries).
+     */
+    OpMax,
+
+    /** Wildcard expansion.
+        *
+        *  The pattern is expanded to the matching terms, which are then
+        *  combined with the specified combining operator.
+     */
+    OpWildcard,
+
+    /** Edit distance expansion.
+        *
+        *  Expand to terms within a specified edit distance of a target,
+        *  which are then combined with the specified combining operator.
+        *
+        *  @since Added in Xapian 1.5.0.
+     */
+    OpEditDistance,
+
+    /** Construct an invalid query.
+        *
+        *  This can be useful as a placeholder - for example, RangeProcessor
+        *  uses it as a return value to indicate that a range hasn't been
+        *  recognised.
+     */
+    OpInvalid = 99,
+
+    /// Value returned by Query::get_type() for a term.
+    LeafTerm = 100,
+
+    /// Value returned by Query::get_type() for a PostingSource.
+    LeafPostingSource,
+
+    /// Value returned by Query::get_type() for MatchAll or equivalent.
+    LeafMatchAll,
+
+    /// Value returned by Query::get_type() for MatchNothing or equivalent.
+    LeafMatchNothing,
+}
+
+impl TryFrom<i32> for XapianOp {
+    type Error = i32;
+
+    /// Convert a Xapian::Query::op value, returns the value back if it is unknown
+    fn try_from(op: i32) -> Result<Self, i32> {
+        use XapianOp::*;
+        let op = match op {
+            0 => OpAnd,
+            1 => OpOr,
+            2 => OpAndNot,
+            3 => OpXor,
+            4 => OpAndMaybe,
+            5 => OpFilter,
+            6 => OpNear,
+            7 => OpPhrase,
+            8 => OpValueRange,
+            9 => OpScaleWeight,
+            10 => OpEliteSet,
+            11 => OpValueGe,
+            12 => OpValueLe,
+            13 => OpSynonym,
+            14 => OpMax,
+            15 => OpWildcard,
+            16 => OpEditDistance,
+            99 => OpInvalid,
+            100 => LeafTerm,
+            101 => LeafPostingSource,
+            102 => LeafMatchAll,
+            103 => LeafMatchNothing,
+            _ => return Err(op),
+        };
+        Ok(op)
+    }
+}
+
+/// Flags for OP_WILDCARD and OP_EDIT_DISTANCE queries, OR together one WILDCARD_LIMIT_* and any WILDCARD_PATTERN_* flags
+#[allow(non_camel_case_types)]
+#[repr(i32)]
+#[derive(Debug)]
+pub enum WildcardFlag {
+    /// Throw an error if OP_WILDCARD exceeds its expansion limit.
+    WILDCARD_LIMIT_ERROR = 0x00,
+    /// Stop expanding when OP_WILDCARD reaches its expansion limit.
+    WILDCARD_LIMIT_FIRST = 0x01,
+    /// Limit OP_WILDCARD expansion to the most frequent terms.
+    WILDCARD_LIMIT_MOST_FREQUENT = 0x02,
+    /** Support * which matches 0 or more characters.
+     *
+     *  @since Added in Xapian 1.5.0.
+     */
+    WILDCARD_PATTERN_MULTI = 0x10,
+    /** Support ? which matches a single character.
+     *
+     *  @since Added in Xapian 1.5.0.
+     */
+    WILDCARD_PATTERN_SINGLE = 0x20,
+    /** Enable glob-style wildcarding.
+     *
+     *  @since Added in Xapian 1.5.0.
+     */
+    WILDCARD_PATTERN_GLOB = Self::WILDCARD_PATTERN_MULTI as i32 | Self::WILDCARD_PATTERN_SINGLE as i32,
 }
 
 /// Flags to OR together and pass to TermGenerator::set_flags().
@@ -445,16 +554,32 @@ pub enum QueryParserFeatureFlag {
     FLAG_DEFAULT = Self::FLAG_PHRASE as i32 | Self::FLAG_BOOLEAN as i32 | Self::FLAG_LOVEHATE as i32,
 }
 
-/// Enum of possible query operations
-#[allow(non_camel_case_types)]
-/// #[repr(i32)]
-pub enum RangeProcessorFlags {
-    RP_PREFIX = 0,
+/// Flags of the range processors, combine them with `|`, e.g. `RP_DATE_PREFER_MDY | RP_SUFFIX`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct RangeProcessorFlags(i32);
+
+impl RangeProcessorFlags {
+    /// the string is a prefix, the default
+    pub const RP_PREFIX: Self = Self(0);
     /// as a suffix
-    RP_SUFFIX = 1,
+    pub const RP_SUFFIX: Self = Self(1);
     /// optionally allow str_ on both ends of the range - e.g. $1..$10 or 5m..50m.
-    RP_REPEATED = 2,
-    RP_DATE_PREFER_MDY = 4,
+    pub const RP_REPEATED: Self = Self(2);
+    /// interpret ambiguous dates such as 01/02/2020 as month/day/year
+    pub const RP_DATE_PREFER_MDY: Self = Self(4);
+
+    /// The flags as the bitmask Xapian takes
+    pub fn bits(self) -> i32 {
+        self.0
+    }
+}
+
+impl std::ops::BitOr for RangeProcessorFlags {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        Self(self.0 | rhs.0)
+    }
 }
 
 #[allow(non_camel_case_types)]
@@ -512,4 +637,92 @@ pub enum EnquireDocidOrder {
     DESCENDING = 0,
     /** docids sort in whatever order is most efficient for the backend. */
     DONT_CARE = 2
-}
\ No newline at end of file
+}
+
+/// Flags to OR together and pass to Enquire::get_eset()
+#[allow(non_camel_case_types)]
+#[repr(i32)]
+#[derive(Debug)]
+pub enum ESetFlag {
+    /** Terms in the query may be returned by get_eset().
+     *
+     *  By default, terms from the query are excluded from the ESet.
+     */
+    INCLUDE_QUERY_TERMS = 1,
+    /** Calculate exact term frequencies in get_eset().
+     *
+     *  By default, when searching a combination of databases, term
+     *  frequencies are approximated, which is faster.
+     */
+    USE_EXACT_TERMFREQ = 2,
+}
+
+/// Statistics a custom [`crate::Weight`] can ask for, see [`crate::Weight::stats_needed`]
+#[allow(non_camel_case_types)]
+#[repr(i32)]
+#[derive(Debug)]
+pub enum WeightStat {
+    /// Number of documents in the collection.
+    COLLECTION_SIZE = 1,
+    /// Number of documents in the RSet.
+    RSET_SIZE = 2,
+    /// Average length of documents in the collection.
+    AVERAGE_LENGTH = 4,
+    /// How many documents the current term is in.
+    TERMFREQ = 8,
+    /// How many documents in the RSet the current term is in.
+    RELTERMFREQ = 16,
+    /// Sum of wqf for terms in the query.
+    QUERY_LENGTH = 32,
+    /// Within-query-frequency of the current term.
+    WQF = 64,
+    /// Within-document-frequency of the current term in the current document.
+    WDF = 128,
+    /// Length of the current document (sum wdf).
+    DOC_LENGTH = 256,
+    /// Lower bound on (non-zero) document lengths.
+    DOC_LENGTH_MIN = 512,
+    /// Upper bound on document lengths.
+    DOC_LENGTH_MAX = 1024,
+    /// Upper bound on wdf of this term.
+    WDF_MAX = 2048,
+    /// Sum of wdf over the whole collection for the current term.
+    COLLECTION_FREQ = 4096,
+    /// Number of unique terms in the current document.
+    UNIQUE_TERMS = 8192,
+}
+
+/// Smoothing methods of [`crate::LMWeight`], the discriminants match Xapian::Weight::type_smoothing
+#[repr(i32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LMSmoothing {
+    #[default]
+    TwoStage = 1,
+    Dirichlet = 2,
+    AbsoluteDiscount = 3,
+    JelinekMercer = 4,
+    DirichletPlus = 5,
+}
+
+/// How a [`crate::TermGenerator`] uses its stopper, the discriminants match Xapian::TermGenerator::stop_strategy
+#[repr(i32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StopStrategy {
+    /// Don't use the stopper
+    StopNone = 0,
+    /// Skip stop words entirely, the default
+    StopAll = 1,
+    /// Index stop words unstemmed but not stemmed
+    StopStemmed = 2,
+}
+
+/// Stemming strategies, the discriminants match Xapian::QueryParser::stem_strategy
+#[repr(i32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StemStrategy {
+    StemNone = 0,
+    StemSome = 1,
+    StemAll = 2,
+    StemAllZ = 3,
+    StemSomeFullPos = 4,
+}
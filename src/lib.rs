@@ -1,8 +1,14 @@
 pub mod constants;
 
+use std::any::Any;
+use std::cell::{RefCell, RefMut};
+use std::collections::HashMap;
 use std::error::Error as StdError;
 use std::fmt::{self, Display};
 use std::io;
+use std::ops;
+use std::panic;
+use std::rc::Rc;
 
 use cxx::UniquePtr;
 
@@ -24,11 +30,107 @@ pub(crate) mod ffi {
         pub(crate) type Query;
         pub(crate) type MultiValueKeyMaker;
         pub(crate) type RangeProcessor;
-        pub(crate) type NumberRangeProcessor;
         pub(crate) type MatchSpy;
         pub(crate) type ValueCountMatchSpy;
-        pub(crate) type BoolWeight;
-        pub(crate) type BM25Weight;
+        pub(crate) type PostingSource;
+        pub(crate) type ValueMapPostingSource;
+        pub(crate) type Stopper;
+        pub(crate) type FieldProcessor;
+        pub(crate) type LatLongCoords;
+        pub(crate) type GreatCircleMetric;
+        pub(crate) type LatLongDistanceKeyMaker;
+        pub(crate) type Weight;
+        pub(crate) type RSet;
+        pub(crate) type ESet;
+        pub(crate) type ESetIterator;
+        pub(crate) type Registry;
+        pub(crate) type ValueIterator;
+    }
+
+    extern "C++" {
+        pub(crate) type QueryVector;
+    }
+
+    /// The statistics available to a custom [`Weight`](crate::Weight) when it is initialised
+    ///
+    /// Fields not asked for by [`Weight::stats_needed`](crate::Weight::stats_needed) are zero.
+    #[derive(Debug, Clone, Default)]
+    pub struct WeightStats {
+        /// Number of documents in the collection
+        pub collection_size: u32,
+        /// Number of documents in the RSet
+        pub rset_size: u32,
+        /// Average length of documents in the collection
+        pub average_length: f64,
+        /// How many documents the current term is in
+        pub termfreq: u32,
+        /// How many documents in the RSet the current term is in
+        pub reltermfreq: u32,
+        /// Sum of wdf over the whole collection for the current term
+        pub collection_freq: u32,
+        /// Sum of wqf for terms in the query
+        pub query_length: u32,
+        /// Within-query-frequency of the current term
+        pub wqf: u32,
+        /// Lower bound on (non-zero) document lengths
+        pub doclength_lower_bound: u32,
+        /// Upper bound on document lengths
+        pub doclength_upper_bound: u32,
+        /// Upper bound on wdf of the current term
+        pub wdf_upper_bound: u32,
+        /// Total length of all documents in the collection
+        pub total_length: u64,
+    }
+
+    extern "Rust" {
+        #[cxx_name = "RustExpandDecider"]
+        type ExpandDecider;
+        fn expand_decider_accept(decider: &ExpandDecider, term: &[u8]) -> Result<bool>;
+
+        #[cxx_name = "RustMatchSpy"]
+        type MatchSpyCallback;
+        fn match_spy_observe(spy: &mut MatchSpyCallback, doc: UniquePtr<Document>, wt: f64) -> Result<()>;
+
+        #[cxx_name = "RustMatchDecider"]
+        type MatchDecider;
+        fn match_decider_accept(decider: &MatchDecider, doc: UniquePtr<Document>) -> Result<bool>;
+
+        #[cxx_name = "RustWeight"]
+        type WeightCallback;
+        fn weight_stats_needed(weight: &WeightCallback) -> Result<i32>;
+        fn weight_clone(weight: &WeightCallback) -> Result<Box<WeightCallback>>;
+        fn weight_init(weight: &mut WeightCallback, factor: f64, stats: &WeightStats) -> Result<()>;
+        fn weight_get_sumpart(weight: &WeightCallback, wdf: u32, doclen: u32, uniqterms: u32) -> Result<f64>;
+        fn weight_get_maxpart(weight: &WeightCallback) -> Result<f64>;
+        fn weight_get_sumextra(weight: &WeightCallback, doclen: u32, uniqterms: u32) -> Result<f64>;
+        fn weight_get_maxextra(weight: &WeightCallback) -> Result<f64>;
+
+        #[cxx_name = "RustPostingSource"]
+        type PostingSourceCallback;
+        fn posting_source_init(source: &mut PostingSourceCallback, db: UniquePtr<Database>) -> Result<()>;
+        fn posting_source_get_termfreq_min(source: &PostingSourceCallback) -> Result<u32>;
+        fn posting_source_get_termfreq_est(source: &PostingSourceCallback) -> Result<u32>;
+        fn posting_source_get_termfreq_max(source: &PostingSourceCallback) -> Result<u32>;
+        fn posting_source_get_maxweight(source: &PostingSourceCallback) -> Result<f64>;
+        fn posting_source_get_weight(source: &PostingSourceCallback) -> Result<f64>;
+        fn posting_source_get_docid(source: &PostingSourceCallback) -> Result<u32>;
+        fn posting_source_next(source: &mut PostingSourceCallback, min_wt: f64) -> Result<()>;
+        fn posting_source_skip_to(source: &mut PostingSourceCallback, did: u32, min_wt: f64) -> Result<()>;
+        fn posting_source_check(source: &mut PostingSourceCallback, did: u32, min_wt: f64) -> Result<bool>;
+        fn posting_source_at_end(source: &PostingSourceCallback) -> Result<bool>;
+        fn posting_source_clone(source: &PostingSourceCallback) -> Result<Box<PostingSourceCallback>>;
+
+        #[cxx_name = "RustStopper"]
+        type StopperCallback;
+        fn stopper_is_stop_word(stopper: &StopperCallback, word: &str) -> Result<bool>;
+
+        #[cxx_name = "RustFieldProcessor"]
+        type FieldProcessorCallback;
+        fn field_processor_process(processor: &mut FieldProcessorCallback, term: &str) -> Result<UniquePtr<Query>>;
+
+        #[cxx_name = "RustRangeProcessor"]
+        type RangeProcessorCallback;
+        fn range_processor_check_range(processor: &mut RangeProcessorCallback, begin: &str, end: &str) -> Result<UniquePtr<Query>>;
     }
 
     unsafe extern "C++" {
@@ -41,6 +143,25 @@ pub(crate) mod ffi {
         pub(crate) fn database_close(db: Pin<&mut Database>) -> Result<()>;
         pub(crate) fn new_enquire(db: Pin<&mut Database>) -> Result<UniquePtr<Enquire>>;
         pub(crate) fn add_database(db: Pin<&mut Database>, add_db: Pin<&mut Database>) -> Result<()>;
+        pub(crate) fn database_get_document(db: Pin<&mut Database>, did: u32) -> Result<UniquePtr<Document>>;
+        pub(crate) fn database_get_doccount(db: Pin<&mut Database>) -> Result<u32>;
+        pub(crate) fn database_get_lastdocid(db: Pin<&mut Database>) -> Result<u32>;
+        pub(crate) fn database_get_avlength(db: Pin<&mut Database>) -> Result<f64>;
+        pub(crate) fn database_get_termfreq(db: Pin<&mut Database>, term: &str) -> Result<u32>;
+        pub(crate) fn database_get_collection_freq(db: Pin<&mut Database>, term: &str) -> Result<u32>;
+        pub(crate) fn database_term_exists(db: Pin<&mut Database>, term: &str) -> Result<bool>;
+        pub(crate) fn database_get_value_freq(db: Pin<&mut Database>, slot: u32) -> Result<u32>;
+        pub(crate) fn database_get_value_lower_bound(db: Pin<&mut Database>, slot: u32) -> Result<Vec<u8>>;
+        pub(crate) fn database_get_value_upper_bound(db: Pin<&mut Database>, slot: u32) -> Result<Vec<u8>>;
+        pub(crate) fn database_get_doclength(db: Pin<&mut Database>, did: u32) -> Result<u32>;
+        pub(crate) fn database_get_uuid(db: Pin<&mut Database>) -> Result<String>;
+        pub(crate) fn database_get_revision(db: Pin<&mut Database>) -> Result<u64>;
+        pub(crate) fn database_get_metadata(db: Pin<&mut Database>, key: &str) -> Result<Vec<u8>>;
+        pub(crate) fn database_metadata_keys_begin(db: Pin<&mut Database>, prefix: &str) -> Result<UniquePtr<TermIterator>>;
+        pub(crate) fn database_spellings_begin(db: Pin<&mut Database>) -> Result<UniquePtr<TermIterator>>;
+        pub(crate) fn database_get_spelling_suggestion(db: Pin<&mut Database>, word: &str, max_edit_distance: u32) -> Result<String>;
+        pub(crate) fn database_synonyms_begin(db: Pin<&mut Database>, term: &str) -> Result<UniquePtr<TermIterator>>;
+        pub(crate) fn database_synonym_keys_begin(db: Pin<&mut Database>, prefix: &str) -> Result<UniquePtr<TermIterator>>;
 
         pub(crate) fn new_stem(lang: &str) -> Result<UniquePtr<Stem>>;
 
@@ -52,6 +173,23 @@ pub(crate) mod ffi {
 
         pub(crate) fn delete_document(db: Pin<&mut WritableDatabase>, unique_term: &str) -> Result<()>;
         pub(crate) fn get_doccount(db: Pin<&mut WritableDatabase>) -> Result<usize>;
+        pub(crate) fn writable_database_add_document(db: Pin<&mut WritableDatabase>, doc: Pin<&mut Document>) -> Result<u32>;
+        pub(crate) fn writable_database_replace_document_by_docid(db: Pin<&mut WritableDatabase>, did: u32, doc: Pin<&mut Document>) -> Result<()>;
+        pub(crate) fn writable_database_delete_document_by_docid(db: Pin<&mut WritableDatabase>, did: u32) -> Result<()>;
+        pub(crate) fn writable_database_begin_transaction(db: Pin<&mut WritableDatabase>, flushed: bool) -> Result<()>;
+        pub(crate) fn writable_database_commit_transaction(db: Pin<&mut WritableDatabase>) -> Result<()>;
+        pub(crate) fn writable_database_cancel_transaction(db: Pin<&mut WritableDatabase>) -> Result<()>;
+        pub(crate) fn writable_database_set_metadata(db: Pin<&mut WritableDatabase>, key: &str, metadata: &[u8]) -> Result<()>;
+        pub(crate) fn writable_database_get_metadata(db: Pin<&mut WritableDatabase>, key: &str) -> Result<Vec<u8>>;
+        pub(crate) fn writable_database_metadata_keys_begin(db: Pin<&mut WritableDatabase>, prefix: &str) -> Result<UniquePtr<TermIterator>>;
+        pub(crate) fn writable_database_add_spelling(db: Pin<&mut WritableDatabase>, word: &str, freqinc: u32) -> Result<()>;
+        pub(crate) fn writable_database_remove_spelling(db: Pin<&mut WritableDatabase>, word: &str, freqdec: u32) -> Result<()>;
+        pub(crate) fn writable_database_add_synonym(db: Pin<&mut WritableDatabase>, term: &str, synonym: &str) -> Result<()>;
+        pub(crate) fn writable_database_remove_synonym(db: Pin<&mut WritableDatabase>, term: &str, synonym: &str) -> Result<()>;
+        pub(crate) fn writable_database_clear_synonyms(db: Pin<&mut WritableDatabase>, term: &str) -> Result<()>;
+        pub(crate) fn writable_database_spellings_begin(db: Pin<&mut WritableDatabase>) -> Result<UniquePtr<TermIterator>>;
+        pub(crate) fn writable_database_synonyms_begin(db: Pin<&mut WritableDatabase>, term: &str) -> Result<UniquePtr<TermIterator>>;
+        pub(crate) fn writable_database_synonym_keys_begin(db: Pin<&mut WritableDatabase>, prefix: &str) -> Result<UniquePtr<TermIterator>>;
 
         pub(crate) fn new_termgenerator() -> Result<UniquePtr<TermGenerator>>;
         pub(crate) fn set_stemmer(tg: Pin<&mut TermGenerator>, stem: Pin<&mut Stem>) -> Result<()>;
@@ -63,6 +201,8 @@ pub(crate) mod ffi {
         pub(crate) fn index_long(tg: Pin<&mut TermGenerator>, data: i64, prefix: &str) -> Result<()>;
         pub(crate) fn index_float(tg: Pin<&mut TermGenerator>, data: f32, prefix: &str) -> Result<()>;
         pub(crate) fn index_double(tg: Pin<&mut TermGenerator>, data: f64, prefix: &str) -> Result<()>;
+        pub(crate) fn term_generator_set_stopper(tg: Pin<&mut TermGenerator>, stopper: UniquePtr<Stopper>) -> Result<()>;
+        pub(crate) fn term_generator_set_stopper_strategy(tg: Pin<&mut TermGenerator>, strategy: i32) -> Result<()>;
 
         pub(crate) fn new_document() -> Result<UniquePtr<Document>>;
         pub(crate) fn add_string(doc: Pin<&mut Document>, slot: u32, data: &str) -> Result<()>;
@@ -72,6 +212,25 @@ pub(crate) mod ffi {
         pub(crate) fn set_data(doc: Pin<&mut Document>, data: &str) -> Result<()>;
         pub(crate) fn get_doc_data(doc: Pin<&mut Document>) -> Result<String>;
         pub(crate) fn add_boolean_term(doc: Pin<&mut Document>, data: &str) -> Result<()>;
+        pub(crate) fn document_set_data_bytes(doc: Pin<&mut Document>, data: &[u8]) -> Result<()>;
+        pub(crate) fn document_get_data_bytes(doc: Pin<&mut Document>) -> Result<Vec<u8>>;
+        pub(crate) fn document_add_value_bytes(doc: Pin<&mut Document>, slot: u32, value: &[u8]) -> Result<()>;
+        pub(crate) fn document_get_value_bytes(doc: Pin<&mut Document>, slot: u32) -> Result<Vec<u8>>;
+        pub(crate) fn document_get_docid(doc: Pin<&mut Document>) -> Result<u32>;
+        pub(crate) fn document_add_term(doc: Pin<&mut Document>, term: &str, wdf_inc: u32) -> Result<()>;
+        pub(crate) fn document_add_posting(doc: Pin<&mut Document>, term: &str, pos: u32, wdf_inc: u32) -> Result<()>;
+        pub(crate) fn document_remove_term(doc: Pin<&mut Document>, term: &str) -> Result<()>;
+        pub(crate) fn document_remove_posting(doc: Pin<&mut Document>, term: &str, pos: u32, wdf_dec: u32) -> Result<()>;
+        pub(crate) fn document_clear_terms(doc: Pin<&mut Document>) -> Result<()>;
+        pub(crate) fn document_termlist_count(doc: Pin<&mut Document>) -> Result<u32>;
+        pub(crate) fn document_termlist_begin(doc: Pin<&mut Document>) -> Result<UniquePtr<TermIterator>>;
+        pub(crate) fn document_termlist_end(doc: Pin<&mut Document>) -> Result<UniquePtr<TermIterator>>;
+        pub(crate) fn document_get_value(doc: Pin<&mut Document>, slot: u32) -> Result<String>;
+        pub(crate) fn document_remove_value(doc: Pin<&mut Document>, slot: u32) -> Result<()>;
+        pub(crate) fn document_clear_values(doc: Pin<&mut Document>) -> Result<()>;
+        pub(crate) fn document_values_count(doc: Pin<&mut Document>) -> Result<u32>;
+        pub(crate) fn document_values_begin(doc: Pin<&mut Document>) -> Result<UniquePtr<ValueIterator>>;
+        pub(crate) fn document_values_end(doc: Pin<&mut Document>) -> Result<UniquePtr<ValueIterator>>;
 
         pub(crate) fn get_matches_estimated(set: Pin<&mut MSet>) -> Result<i32>;
         pub(crate) fn mset_size(set: Pin<&mut MSet>) -> Result<i32>;
@@ -88,23 +247,69 @@ pub(crate) mod ffi {
         pub(crate) fn mset_iterator_get_document(iter: Pin<&mut MSetIterator>) -> Result<UniquePtr<Document>>;
         pub(crate) fn mset_iterator_eq(iter: Pin<&mut MSetIterator>, other: Pin<&mut MSetIterator>) -> Result<bool>;
         pub(crate) fn mset_iterator_next(iter: Pin<&mut MSetIterator>) -> Result<()>;
+        pub(crate) fn mset_iterator_get_docid(iter: Pin<&mut MSetIterator>) -> Result<u32>;
+        pub(crate) fn mset_iterator_get_rank(iter: Pin<&mut MSetIterator>) -> Result<u32>;
+        pub(crate) fn mset_iterator_get_weight(iter: Pin<&mut MSetIterator>) -> Result<f64>;
+        pub(crate) fn mset_iterator_get_percent(iter: Pin<&mut MSetIterator>) -> Result<i32>;
+        pub(crate) fn mset_iterator_get_collapse_count(iter: Pin<&mut MSetIterator>) -> Result<u32>;
+        pub(crate) fn mset_iterator_get_collapse_key(iter: Pin<&mut MSetIterator>) -> Result<Vec<u8>>;
 
         pub(crate) fn mset_begin(set: Pin<&mut MSet>) -> Result<UniquePtr<MSetIterator>>;
         pub(crate) fn mset_end(set: Pin<&mut MSet>) -> Result<UniquePtr<MSetIterator>>;
         pub(crate) fn mset_back(set: Pin<&mut MSet>) -> Result<UniquePtr<MSetIterator>>;
+        pub(crate) fn mset_iterator_at(set: Pin<&mut MSet>, index: u32) -> Result<UniquePtr<MSetIterator>>;
+        pub(crate) fn mset_clone(set: &MSet) -> UniquePtr<MSet>;
         // pub(crate) fn get_doc_by_index(set: Pin<&mut MSet>, index: i32) -> Result<UniquePtr<Document>>;
 
         pub(crate) fn get_mset(en: Pin<&mut Enquire>, from: i32, size: i32) -> Result<UniquePtr<MSet>>;
         pub(crate) fn set_query(en: Pin<&mut Enquire>, query: Pin<&mut Query>) -> Result<()>;
         pub(crate) fn set_sort_by_key(en: Pin<&mut Enquire>, sorter: Pin<&mut MultiValueKeyMaker>, reverse: bool) -> Result<()>;
+        pub(crate) fn enquire_set_sort_by_lat_long_distance_key(en: Pin<&mut Enquire>, sorter: Pin<&mut LatLongDistanceKeyMaker>, reverse: bool) -> Result<()>;
         pub(crate) fn add_matchspy_value_count(en: Pin<&mut Enquire>, vcms: Pin<&mut ValueCountMatchSpy>) -> Result<()>;
-        pub(crate) fn enquire_set_weighting_scheme_bool(en: Pin<&mut Enquire>, bw: Pin<&mut BoolWeight>)-> Result<()>;
-        pub(crate) fn enquire_set_weighting_scheme_bm25(en: Pin<&mut Enquire>, bw: Pin<&mut BM25Weight>)-> Result<()>;
+        pub(crate) fn new_rust_match_spy(spy: Box<MatchSpyCallback>) -> Result<UniquePtr<MatchSpy>>;
+        pub(crate) fn enquire_add_matchspy(en: Pin<&mut Enquire>, spy: Pin<&mut MatchSpy>) -> Result<()>;
+        pub(crate) fn enquire_set_weighting_scheme(en: Pin<&mut Enquire>, weight: &Weight) -> Result<()>;
+        pub(crate) fn enquire_set_weighting_scheme_custom(en: Pin<&mut Enquire>, weight: Box<WeightCallback>) -> Result<()>;
         pub(crate) fn enquire_set_docid_order(en: Pin<&mut Enquire>, order: i32) -> Result<()>;
         pub(crate) fn enquire_set_sort_by_relevance(en: Pin<&mut Enquire>) -> Result<()>;
         pub(crate) fn enquire_set_sort_by_value(en: Pin<&mut Enquire>, sort_key: u32, reverse: bool) -> Result<()>;
         pub(crate) fn enquire_set_sort_by_relevance_then_value(en: Pin<&mut Enquire>, sort_key: u32, reverse: bool) -> Result<()>;
         pub(crate) fn enquire_set_collapse_key(en: Pin<&mut Enquire>, collapse_key: u32, collapse_max: u32)-> Result<()>;
+        pub(crate) fn enquire_get_mset_with_rset(en: Pin<&mut Enquire>, from: u32, size: u32, rset: Pin<&mut RSet>) -> Result<UniquePtr<MSet>>;
+        pub(crate) fn enquire_get_mset_with_decider(
+            en: Pin<&mut Enquire>,
+            from: u32,
+            size: u32,
+            checkatleast: u32,
+            decider: &MatchDecider,
+        ) -> Result<UniquePtr<MSet>>;
+        pub(crate) fn enquire_get_eset(en: Pin<&mut Enquire>, maxitems: u32, rset: Pin<&mut RSet>, flags: i32, min_wt: f64) -> Result<UniquePtr<ESet>>;
+        pub(crate) fn enquire_get_eset_with_decider(
+            en: Pin<&mut Enquire>,
+            maxitems: u32,
+            rset: Pin<&mut RSet>,
+            flags: i32,
+            decider: &ExpandDecider,
+            min_wt: f64,
+        ) -> Result<UniquePtr<ESet>>;
+
+        pub(crate) fn new_rset() -> Result<UniquePtr<RSet>>;
+        pub(crate) fn rset_add_document(rset: Pin<&mut RSet>, did: u32) -> Result<()>;
+        pub(crate) fn rset_remove_document(rset: Pin<&mut RSet>, did: u32) -> Result<()>;
+        pub(crate) fn rset_contains(rset: Pin<&mut RSet>, did: u32) -> Result<bool>;
+        pub(crate) fn rset_size(rset: Pin<&mut RSet>) -> Result<u32>;
+        pub(crate) fn rset_empty(rset: Pin<&mut RSet>) -> Result<bool>;
+
+        pub(crate) fn eset_size(set: Pin<&mut ESet>) -> Result<u32>;
+        pub(crate) fn eset_get_ebound(set: Pin<&mut ESet>) -> Result<u32>;
+        pub(crate) fn eset_clone(set: &ESet) -> UniquePtr<ESet>;
+        pub(crate) fn eset_begin(set: Pin<&mut ESet>) -> Result<UniquePtr<ESetIterator>>;
+        pub(crate) fn eset_end(set: Pin<&mut ESet>) -> Result<UniquePtr<ESetIterator>>;
+        pub(crate) fn eset_iterator_at(set: Pin<&mut ESet>, index: u32) -> Result<UniquePtr<ESetIterator>>;
+        pub(crate) fn eset_iterator_get_term(iter: Pin<&mut ESetIterator>) -> Result<String>;
+        pub(crate) fn eset_iterator_get_weight(iter: Pin<&mut ESetIterator>) -> Result<f64>;
+        pub(crate) fn eset_iterator_eq(iter: Pin<&mut ESetIterator>, other: Pin<&mut ESetIterator>) -> bool;
+        pub(crate) fn eset_iterator_next(iter: Pin<&mut ESetIterator>) -> Result<()>;
 
         pub(crate) fn new_query_parser() -> Result<UniquePtr<QueryParser>>;
         pub(crate) fn set_max_wildcard_expansion(qp: Pin<&mut QueryParser>, limit: i32) -> Result<()>;
@@ -112,15 +317,57 @@ pub(crate) mod ffi {
         pub(crate) fn set_database(qp: Pin<&mut QueryParser>, add_db: Pin<&mut Database>) -> Result<()>;
         pub(crate) fn add_prefix(qp: Pin<&mut QueryParser>, field: &str, prefix: &str) -> Result<()>;
         pub(crate) fn add_boolean_prefix(qp: Pin<&mut QueryParser>, field: &str, prefix: &str) -> Result<()>;
-        pub(crate) fn add_rangeprocessor(qp: Pin<&mut QueryParser>, range_proc: Pin<&mut RangeProcessor>) -> Result<()>;
-        pub(crate) fn add_number_rangeprocessor(qp: Pin<&mut QueryParser>, range_proc: Pin<&mut NumberRangeProcessor>) -> Result<()>;
+        pub(crate) fn new_rust_field_processor(processor: Box<FieldProcessorCallback>) -> Result<UniquePtr<FieldProcessor>>;
+        pub(crate) fn query_parser_add_prefix_processor(qp: Pin<&mut QueryParser>, field: &str, processor: UniquePtr<FieldProcessor>) -> Result<()>;
+        pub(crate) fn query_parser_add_boolean_prefix_processor(qp: Pin<&mut QueryParser>, field: &str, processor: UniquePtr<FieldProcessor>) -> Result<()>;
         pub(crate) fn parse_query(qp: Pin<&mut QueryParser>, query_string: &str, flags: i32) -> Result<UniquePtr<Query>>;
         pub(crate) fn parse_query_with_prefix(qp: Pin<&mut QueryParser>, query_string: &str, flags: i32, prefix: &str) -> Result<UniquePtr<Query>>;
+        pub(crate) fn query_parser_get_corrected_query_string(qp: Pin<&mut QueryParser>) -> Result<String>;
+        pub(crate) fn query_parser_set_stopper(qp: Pin<&mut QueryParser>, stopper: UniquePtr<Stopper>) -> Result<()>;
+        pub(crate) fn query_parser_stoplist_begin(qp: Pin<&mut QueryParser>) -> Result<UniquePtr<TermIterator>>;
+        pub(crate) fn query_parser_stoplist_end(qp: Pin<&mut QueryParser>) -> Result<UniquePtr<TermIterator>>;
+
+        pub(crate) fn new_simple_stopper() -> Result<UniquePtr<Stopper>>;
+        pub(crate) fn simple_stopper_add(stopper: Pin<&mut Stopper>, word: &str) -> Result<()>;
+        pub(crate) fn new_stem_stopper(stemmer: &Stem, strategy: i32) -> Result<UniquePtr<Stopper>>;
+        pub(crate) fn stem_stopper_add(stopper: Pin<&mut Stopper>, word: &str) -> Result<()>;
+        pub(crate) fn new_rust_stopper(stopper: Box<StopperCallback>) -> Result<UniquePtr<Stopper>>;
+        pub(crate) fn stopper_is_stop(stopper: &Stopper, word: &str) -> Result<bool>;
 
         pub(crate) fn new_query() -> Result<UniquePtr<Query>>;
         pub(crate) fn new_query_range(op: i32, slot: u32, begin: f64, end: f64) -> Result<UniquePtr<Query>>;
-        pub(crate) fn add_right_query(this_q: Pin<&mut Query>, op: i32, q: Pin<&mut Query>) -> Result<UniquePtr<Query>>;
+        pub(crate) fn add_right_query(this_q: &Query, op: i32, q: &Query) -> Result<UniquePtr<Query>>;
         pub(crate) fn new_query_double_with_prefix(prefix: &str, d: f64) -> Result<UniquePtr<Query>>;
+        pub(crate) fn new_query_term(term: &str, wqf: u32, pos: u32) -> Result<UniquePtr<Query>>;
+        pub(crate) fn new_query_match_all() -> Result<UniquePtr<Query>>;
+        pub(crate) fn new_query_from_posting_source(source: Box<PostingSourceCallback>) -> Result<UniquePtr<Query>>;
+        pub(crate) fn new_query_from_builtin_posting_source(source: UniquePtr<PostingSource>) -> Result<UniquePtr<Query>>;
+        pub(crate) fn new_query_from_value_map_posting_source(source: UniquePtr<ValueMapPostingSource>) -> Result<UniquePtr<Query>>;
+        pub(crate) fn new_query_scale_weight(factor: f64, subquery: &Query) -> Result<UniquePtr<Query>>;
+        pub(crate) fn new_query_wildcard(pattern: &str, max_expansion: u32, flags: i32, combiner: i32) -> Result<UniquePtr<Query>>;
+        pub(crate) fn new_query_edit_distance(
+            pattern: &str,
+            edit_distance: u32,
+            max_expansion: u32,
+            flags: i32,
+            combiner: i32,
+            min_prefix_len: usize,
+        ) -> Result<UniquePtr<Query>>;
+        pub(crate) fn new_query_from_vector(op: i32, subqueries: &QueryVector, parameter: u32) -> Result<UniquePtr<Query>>;
+        pub(crate) fn new_query_vector() -> UniquePtr<QueryVector>;
+        pub(crate) fn query_vector_push(subqueries: Pin<&mut QueryVector>, q: &Query);
+        pub(crate) fn query_serialise(q: &Query) -> Result<Vec<u8>>;
+        pub(crate) fn query_unserialise(serialised: &[u8], registry: &Registry) -> Result<UniquePtr<Query>>;
+        pub(crate) fn query_get_type(q: &Query) -> i32;
+        pub(crate) fn query_get_num_subqueries(q: &Query) -> usize;
+        pub(crate) fn query_get_subquery(q: &Query, n: usize) -> Result<UniquePtr<Query>>;
+        pub(crate) fn query_get_leaf_wqf(q: &Query) -> Result<u32>;
+        pub(crate) fn query_get_leaf_pos(q: &Query) -> Result<u32>;
+        pub(crate) fn query_get_length(q: &Query) -> u32;
+        pub(crate) fn query_get_terms_begin(q: &Query) -> Result<UniquePtr<TermIterator>>;
+        pub(crate) fn query_get_terms_end(q: &Query) -> Result<UniquePtr<TermIterator>>;
+        pub(crate) fn query_get_unique_terms_begin(q: &Query) -> Result<UniquePtr<TermIterator>>;
+        pub(crate) fn query_get_unique_terms_end(q: &Query) -> Result<UniquePtr<TermIterator>>;
         pub(crate) fn query_is_empty(this_q: Pin<&mut Query>) -> bool;
         pub(crate) fn get_description(this_q: Pin<&mut Query>) -> String;
 
@@ -128,23 +375,85 @@ pub(crate) mod ffi {
         pub(crate) fn add_value_to_multi_value_key_maker(this_m: Pin<&mut MultiValueKeyMaker>, slot: u32, asc_desc: bool) -> Result<()>;
 
         pub(crate) fn new_value_count_match_spy(slot: u32) -> Result<UniquePtr<ValueCountMatchSpy>>;
-        pub(crate) fn new_range_processor(slot: u32, prefix: &str, flags: i32) -> Result<UniquePtr<RangeProcessor>>;
-        pub(crate) fn new_number_range_processor(slot: u32, prefix: &str, flags: i32) -> Result<UniquePtr<NumberRangeProcessor>>;
+
+        pub(crate) fn new_value_weight_posting_source(slot: u32) -> Result<UniquePtr<PostingSource>>;
+        pub(crate) fn new_decreasing_value_weight_posting_source(slot: u32, range_start: u32, range_end: u32) -> Result<UniquePtr<PostingSource>>;
+        pub(crate) fn new_fixed_weight_posting_source(wt: f64) -> Result<UniquePtr<PostingSource>>;
+        pub(crate) fn new_value_map_posting_source(slot: u32) -> Result<UniquePtr<ValueMapPostingSource>>;
+        pub(crate) fn new_lat_long_distance_posting_source(
+            slot: u32,
+            centre: &LatLongCoords,
+            metric: &GreatCircleMetric,
+            max_range: f64,
+            k1: f64,
+            k2: f64,
+        ) -> Result<UniquePtr<PostingSource>>;
+        pub(crate) fn value_map_posting_source_add_mapping(source: Pin<&mut ValueMapPostingSource>, key: &str, wt: f64) -> Result<()>;
+        pub(crate) fn value_map_posting_source_clear_mappings(source: Pin<&mut ValueMapPostingSource>) -> Result<()>;
+        pub(crate) fn value_map_posting_source_set_default_weight(source: Pin<&mut ValueMapPostingSource>, wt: f64) -> Result<()>;
+        pub(crate) fn new_rust_range_processor(prefix: &str, flags: i32, processor: Box<RangeProcessorCallback>) -> Result<UniquePtr<RangeProcessor>>;
+        pub(crate) fn query_parser_add_rangeprocessor(
+            qp: Pin<&mut QueryParser>,
+            range_proc: UniquePtr<RangeProcessor>,
+            has_grouping: bool,
+            grouping: &str,
+        ) -> Result<()>;
+        pub(crate) fn new_number_range_processor(slot: u32, prefix: &str, flags: i32) -> Result<UniquePtr<RangeProcessor>>;
+        pub(crate) fn new_date_range_processor(slot: u32, prefix: &str, flags: i32, epoch_year: i32) -> Result<UniquePtr<RangeProcessor>>;
+        pub(crate) fn new_unit_range_processor(slot: u32, prefix: &str) -> Result<UniquePtr<RangeProcessor>>;
 
         pub(crate) fn value_count_matchspy_values_begin(vcms: Pin<&mut ValueCountMatchSpy>) -> Result<UniquePtr<TermIterator>>;
         pub(crate) fn value_count_matchspy_values_end(vcms: Pin<&mut ValueCountMatchSpy>) -> Result<UniquePtr<TermIterator>>;
         pub(crate) fn value_count_matchspy_get_total(vcms: Pin<&mut ValueCountMatchSpy>) -> i32;
 
-        pub(crate) fn term_iterator_get_termfreq_value(titer: Pin<&mut TermIterator>) -> String;
-        pub(crate) fn term_iterator_get_termfreq_freq(titer: Pin<&mut TermIterator>) -> i32;
+        pub(crate) fn term_iterator_get_termfreq_value(titer: Pin<&mut TermIterator>) -> Result<String>;
+        pub(crate) fn term_iterator_get_termfreq_freq(titer: Pin<&mut TermIterator>) -> Result<i32>;
         pub(crate) fn term_iterator_eq(titer: Pin<&mut TermIterator>, other: Pin<&mut TermIterator>) -> bool;
-        pub(crate) fn term_iterator_next(titer: Pin<&mut TermIterator>);
+        pub(crate) fn new_term_iterator_end() -> Result<UniquePtr<TermIterator>>;
+        pub(crate) fn term_iterator_get_term(titer: Pin<&mut TermIterator>) -> Result<String>;
+        pub(crate) fn term_iterator_get_wdf(titer: Pin<&mut TermIterator>) -> Result<u32>;
+        pub(crate) fn term_iterator_next(titer: Pin<&mut TermIterator>) -> Result<()>;
+
+        pub(crate) fn value_iterator_get_value(viter: Pin<&mut ValueIterator>) -> Result<Vec<u8>>;
+        pub(crate) fn value_iterator_get_valueno(viter: Pin<&mut ValueIterator>) -> Result<u32>;
+        pub(crate) fn value_iterator_eq(viter: Pin<&mut ValueIterator>, other: Pin<&mut ValueIterator>) -> bool;
+        pub(crate) fn value_iterator_next(viter: Pin<&mut ValueIterator>) -> Result<()>;
+
+        pub(crate) fn new_registry() -> Result<UniquePtr<Registry>>;
+
+        pub(crate) fn new_lat_long_coords() -> Result<UniquePtr<LatLongCoords>>;
+        pub(crate) fn lat_long_coords_append(coords: Pin<&mut LatLongCoords>, latitude: f64, longitude: f64) -> Result<()>;
+        pub(crate) fn lat_long_coords_size(coords: &LatLongCoords) -> usize;
+        pub(crate) fn lat_long_coords_flatten(coords: &LatLongCoords) -> Result<Vec<f64>>;
+        pub(crate) fn lat_long_coords_serialise(coords: &LatLongCoords) -> Result<Vec<u8>>;
+        pub(crate) fn lat_long_coords_unserialise(coords: Pin<&mut LatLongCoords>, data: &[u8]) -> Result<()>;
+        pub(crate) fn new_great_circle_metric() -> Result<UniquePtr<GreatCircleMetric>>;
+        pub(crate) fn new_great_circle_metric_with_radius(radius: f64) -> Result<UniquePtr<GreatCircleMetric>>;
+        pub(crate) fn great_circle_metric_distance(metric: &GreatCircleMetric, a: &LatLongCoords, b: &LatLongCoords) -> Result<f64>;
+        pub(crate) fn new_lat_long_distance_key_maker(
+            slot: u32,
+            centre: &LatLongCoords,
+            metric: &GreatCircleMetric,
+            has_defdistance: bool,
+            defdistance: f64,
+        ) -> Result<UniquePtr<LatLongDistanceKeyMaker>>;
 
         // Weight
-        // BoolWeight
-        pub(crate) fn new_bool_weight() -> Result<UniquePtr<BoolWeight>>;
-        // BM25
-        pub(crate) fn new_bm25_weight(k1: f64, k2: f64, k3: f64, b: f64, min_normlen: f64) -> Result<UniquePtr<BM25Weight>>;
+        pub(crate) fn new_bool_weight() -> Result<UniquePtr<Weight>>;
+        pub(crate) fn new_tfidf_weight(normalizations: &str) -> Result<UniquePtr<Weight>>;
+        pub(crate) fn new_bm25_weight(k1: f64, k2: f64, k3: f64, b: f64, min_normlen: f64) -> Result<UniquePtr<Weight>>;
+        pub(crate) fn new_bm25plus_weight(k1: f64, k2: f64, k3: f64, b: f64, min_normlen: f64, delta: f64) -> Result<UniquePtr<Weight>>;
+        pub(crate) fn new_inl2_weight(c: f64) -> Result<UniquePtr<Weight>>;
+        pub(crate) fn new_ifb2_weight(c: f64) -> Result<UniquePtr<Weight>>;
+        pub(crate) fn new_ineb2_weight(c: f64) -> Result<UniquePtr<Weight>>;
+        pub(crate) fn new_bb2_weight(c: f64) -> Result<UniquePtr<Weight>>;
+        pub(crate) fn new_dlh_weight() -> Result<UniquePtr<Weight>>;
+        pub(crate) fn new_pl2_weight(c: f64) -> Result<UniquePtr<Weight>>;
+        pub(crate) fn new_pl2plus_weight(c: f64, delta: f64) -> Result<UniquePtr<Weight>>;
+        pub(crate) fn new_dph_weight() -> Result<UniquePtr<Weight>>;
+        pub(crate) fn new_lm_weight(param_log: f64, smoothing: i32, smoothing1: f64, smoothing2: f64) -> Result<UniquePtr<Weight>>;
+        pub(crate) fn new_coord_weight() -> Result<UniquePtr<Weight>>;
+        pub(crate) fn new_dice_coeff_weight() -> Result<UniquePtr<Weight>>;
     }
 }
 
@@ -152,51 +461,484 @@ pub fn version_string() ->&'static str {
     ffi::version_string()
 }
 
+/// The details every `Xapian::Error` carries, see
+/// https://xapian.org/docs/apidoc/html/classXapian_1_1Error.html
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ErrorInfo {
+    /// The class name of the error, e.g. "DatabaseModifiedError"
+    pub type_name: String,
+    /// The message explaining what went wrong
+    pub msg: String,
+    /// Optional context information, e.g. the remote database an error came from
+    pub context: String,
+    /// The errno value Xapian recorded with the error, if any
+    pub errno: Option<i32>,
+    /// The description of the errno value Xapian recorded with the error, if any
+    pub error_string: Option<String>,
+}
+
+/// Error type of this crate, one variant per subclass of `Xapian::Error` declared in `xapian/error.h`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+    /// AssertionError is thrown if a logical assertion inside Xapian fails
+    Assertion(ErrorInfo),
+    /// InvalidArgumentError indicates an invalid parameter value was passed to the API
+    InvalidArgument(ErrorInfo),
+    /// InvalidOperationError indicates the API was used in an invalid way
+    InvalidOperation(ErrorInfo),
+    /// UnimplementedError indicates an attempt to use an unimplemented feature
+    Unimplemented(ErrorInfo),
+    /// DatabaseError indicates some sort of database related error
+    Database(ErrorInfo),
+    /// DatabaseCorruptError indicates database corruption was detected
+    DatabaseCorrupt(ErrorInfo),
+    /// DatabaseCreateError indicates a failure to create a database
+    DatabaseCreate(ErrorInfo),
+    /// DatabaseLockError indicates failure to lock a database
+    DatabaseLock(ErrorInfo),
+    /// DatabaseModifiedError indicates a database was modified, call `reopen()` and retry
+    DatabaseModified(ErrorInfo),
+    /// DatabaseOpeningError indicates failure to open a database
+    DatabaseOpening(ErrorInfo),
+    /// DatabaseVersionError indicates that a database is in an unsupported format
+    DatabaseVersion(ErrorInfo),
+    /// DocNotFoundError indicates that a document was requested which doesn't exist
+    DocNotFound(ErrorInfo),
+    /// FeatureUnavailableError indicates that a feature isn't available in this build
+    FeatureUnavailable(ErrorInfo),
+    /// InternalError indicates a runtime problem of some sort
+    Internal(ErrorInfo),
+    /// NetworkError indicates a problem communicating with a remote database
+    Network(ErrorInfo),
+    /// NetworkTimeoutError indicates a timeout expired while communicating with a remote database
+    NetworkTimeout(ErrorInfo),
+    /// QueryParserError indicates a query string can't be parsed
+    QueryParser(ErrorInfo),
+    /// SerialisationError indicates an error unserialising some data
+    Serialisation(ErrorInfo),
+    /// RangeError indicates an attempt to access outside the bounds of a container
+    Range(ErrorInfo),
+    /// WildcardError indicates an error expanding a wildcarded query
+    Wildcard(ErrorInfo),
+    /// DatabaseNotFoundError indicates an attempt to open a database which doesn't exist
+    DatabaseNotFound(ErrorInfo),
+    /// DatabaseClosedError indicates an attempt to access a closed database
+    DatabaseClosed(ErrorInfo),
+    /// Any other exception thrown by the C++ side, e.g. `std::bad_alloc`
+    Cxx(String),
+    /// An I/O error on the rust side, e.g. reading the file given to [`SimpleStopper::from_file`]
+    Io { kind: io::ErrorKind, msg: String },
+}
+
+impl Error {
+    /// Rebuild the error from the NUL separated `what()` string produced by
+    /// `rust::behavior::trycatch` in xapian-bind.h
+    fn from_what(what: &str) -> Self {
+        let mut parts = what.splitn(5, '\0');
+        let (Some(type_name), Some(msg), Some(context), Some(errno), Some(error_string)) =
+            (parts.next(), parts.next(), parts.next(), parts.next(), parts.next())
+        else {
+            return Error::Cxx(what.to_string());
+        };
+        let info = ErrorInfo {
+            type_name: type_name.to_string(),
+            msg: msg.to_string(),
+            context: context.to_string(),
+            errno: errno.parse().ok().filter(|&errno| errno != 0),
+            error_string: if error_string.is_empty() { None } else { Some(error_string.to_string()) },
+        };
+        match type_name {
+            "AssertionError" => Error::Assertion(info),
+            "InvalidArgumentError" => Error::InvalidArgument(info),
+            "InvalidOperationError" => Error::InvalidOperation(info),
+            "UnimplementedError" => Error::Unimplemented(info),
+            "DatabaseError" => Error::Database(info),
+            "DatabaseCorruptError" => Error::DatabaseCorrupt(info),
+            "DatabaseCreateError" => Error::DatabaseCreate(info),
+            "DatabaseLockError" => Error::DatabaseLock(info),
+            "DatabaseModifiedError" => Error::DatabaseModified(info),
+            "DatabaseOpeningError" => Error::DatabaseOpening(info),
+            "DatabaseVersionError" => Error::DatabaseVersion(info),
+            "DocNotFoundError" => Error::DocNotFound(info),
+            "FeatureUnavailableError" => Error::FeatureUnavailable(info),
+            "InternalError" => Error::Internal(info),
+            "NetworkError" => Error::Network(info),
+            "NetworkTimeoutError" => Error::NetworkTimeout(info),
+            "QueryParserError" => Error::QueryParser(info),
+            "SerialisationError" => Error::Serialisation(info),
+            "RangeError" => Error::Range(info),
+            "WildcardError" => Error::Wildcard(info),
+            "DatabaseNotFoundError" => Error::DatabaseNotFound(info),
+            "DatabaseClosedError" => Error::DatabaseClosed(info),
+            _ => Error::Cxx(what.to_string()),
+        }
+    }
+
+    /// The Xapian error details, `None` for non-Xapian C++ exceptions
+    pub fn info(&self) -> Option<&ErrorInfo> {
+        match self {
+            Error::Assertion(info)
+            | Error::InvalidArgument(info)
+            | Error::InvalidOperation(info)
+            | Error::Unimplemented(info)
+            | Error::Database(info)
+            | Error::DatabaseCorrupt(info)
+            | Error::DatabaseCreate(info)
+            | Error::DatabaseLock(info)
+            | Error::DatabaseModified(info)
+            | Error::DatabaseOpening(info)
+            | Error::DatabaseVersion(info)
+            | Error::DocNotFound(info)
+            | Error::FeatureUnavailable(info)
+            | Error::Internal(info)
+            | Error::Network(info)
+            | Error::NetworkTimeout(info)
+            | Error::QueryParser(info)
+            | Error::Serialisation(info)
+            | Error::Range(info)
+            | Error::Wildcard(info)
+            | Error::DatabaseNotFound(info)
+            | Error::DatabaseClosed(info) => Some(info),
+            Error::Cxx(_) | Error::Io { .. } => None,
+        }
+    }
+
+    /// A `RangeError` for an index checked on the rust side
+    fn range(msg: String) -> Self {
+        Error::Range(ErrorInfo {
+            type_name: "RangeError".to_string(),
+            msg,
+            context: String::new(),
+            errno: None,
+            error_string: None,
+        })
+    }
+
+    /// An `InvalidOperationError` for a misuse caught on the rust side
+    fn invalid_operation(msg: String) -> Self {
+        Error::InvalidOperation(ErrorInfo {
+            type_name: "InvalidOperationError".to_string(),
+            msg,
+            context: String::new(),
+            errno: None,
+            error_string: None,
+        })
+    }
+
+    /// Whether this is a `Xapian::LogicError`, i.e. a misuse of the API
+    pub fn is_logic_error(&self) -> bool {
+        matches!(self, Error::Assertion(_) | Error::InvalidArgument(_) | Error::InvalidOperation(_) | Error::Unimplemented(_))
+    }
+
+    /// Whether this is a `Xapian::DatabaseError` or one of its subclasses
+    pub fn is_database_error(&self) -> bool {
+        matches!(
+            self,
+            Error::Database(_)
+                | Error::DatabaseCorrupt(_)
+                | Error::DatabaseCreate(_)
+                | Error::DatabaseLock(_)
+                | Error::DatabaseModified(_)
+                | Error::DatabaseOpening(_)
+                | Error::DatabaseVersion(_)
+                | Error::DatabaseNotFound(_)
+                | Error::DatabaseClosed(_)
+        )
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.info() {
+            Some(info) => {
+                write!(f, "[Xapian Error] {}: {}", info.type_name, info.msg)?;
+                if !info.context.is_empty() {
+                    write!(f, " (context: {})", info.context)?;
+                }
+                if let Some(error_string) = &info.error_string {
+                    write!(f, " ({})", error_string)?;
+                }
+                Ok(())
+            }
+            None => match self {
+                Error::Cxx(what) => f.write_str(what),
+                Error::Io { msg, .. } => f.write_str(msg),
+                _ => unreachable!(),
+            },
+        }
+    }
+}
+
+impl StdError for Error {}
+
+impl From<cxx::Exception> for Error {
+    fn from(e: cxx::Exception) -> Self {
+        Error::from_what(e.what())
+    }
+}
+
 #[warn(unused_unsafe)]
 pub struct MultiValueKeyMaker {
     pub cxxp: UniquePtr<ffi::MultiValueKeyMaker>,
 }
 
 impl MultiValueKeyMaker {
-    pub fn new() -> Result<Self, cxx::Exception> {
+    pub fn new() -> Result<Self, Error> {
         Ok(Self {
             cxxp: ffi::new_multi_value_key_maker()?,
         })
     }
 
-    pub fn add_value(&mut self, slot: u32, asc_desc: bool) -> Result<(), cxx::Exception> {
+    pub fn add_value(&mut self, slot: u32, asc_desc: bool) -> Result<(), Error> {
         ffi::add_value_to_multi_value_key_maker(self.cxxp.pin_mut(), slot, asc_desc)?;
         Ok(())
     }
 }
 
+/// A way of generating sort keys, see [`Enquire::set_sort_by_key`]
+///
+/// Implemented by [`MultiValueKeyMaker`] and [`LatLongDistanceKeyMaker`].
+pub trait KeyMaker {
+    #[doc(hidden)]
+    fn set_on(self, enquire: &mut Enquire, reverse: bool) -> Result<(), Error>
+    where
+        Self: Sized;
+}
+
+impl KeyMaker for MultiValueKeyMaker {
+    fn set_on(mut self, enquire: &mut Enquire, reverse: bool) -> Result<(), Error> {
+        ffi::set_sort_by_key(enquire.cxxp.pin_mut(), self.cxxp.pin_mut(), reverse)?;
+        enquire.sorter = Some(Box::new(self));
+        Ok(())
+    }
+}
+
 pub struct Query {
     pub cxxp: UniquePtr<ffi::Query>,
 }
 
 impl Query {
-    pub fn new() -> Result<Self, cxx::Exception> {
+    pub fn new() -> Result<Self, Error> {
         Ok(Self { cxxp: ffi::new_query()? })
     }
 
-    pub fn new_range(op: constants::XapianOp, slot: u32, begin: f64, end: f64) -> Result<Self, cxx::Exception> {
+    pub fn new_range(op: constants::XapianOp, slot: u32, begin: f64, end: f64) -> Result<Self, Error> {
         Ok(Self {
             cxxp: ffi::new_query_range(op as i32, slot, begin, end)?,
         })
     }
 
-    pub fn add_right(&mut self, op: constants::XapianOp, q: &mut Query) -> Result<Self, cxx::Exception> {
+    /// Combine this query with `q` using `op`, neither query is modified
+    pub fn add_right(&self, op: constants::XapianOp, q: &Query) -> Result<Self, Error> {
         Ok(Self {
-            cxxp: ffi::add_right_query(self.cxxp.pin_mut(), op as i32, q.cxxp.pin_mut())?,
+            cxxp: ffi::add_right_query(&self.cxxp, op as i32, &q.cxxp)?,
         })
     }
 
-    pub fn new_double_with_prefix(prefix: &str, d: f64) -> Result<Self, cxx::Exception> {
+    pub fn new_double_with_prefix(prefix: &str, d: f64) -> Result<Self, Error> {
         Ok(Self {
             cxxp: ffi::new_query_double_with_prefix(prefix, d)?,
         })
     }
 
+    /// Construct a query for a single term, with the within-query frequency `wqf`
+    /// and the query position `pos` (0 means unset)
+    pub fn new_term(term: &str, wqf: u32, pos: u32) -> Result<Self, Error> {
+        Ok(Self {
+            cxxp: ffi::new_query_term(term, wqf, pos)?,
+        })
+    }
+
+    /// Construct a query matching all documents, the equivalent of `Xapian::Query::MatchAll`
+    pub fn new_match_all() -> Result<Self, Error> {
+        Ok(Self {
+            cxxp: ffi::new_query_match_all()?,
+        })
+    }
+
+    /// Construct a query matching no documents, the equivalent of `Xapian::Query::MatchNothing`
+    pub fn new_match_nothing() -> Result<Self, Error> {
+        Self::new()
+    }
+
+    /// Construct a query which matches and weights documents as given by a posting source, either a
+    /// built-in one such as [`ValueWeightPostingSource`] or a custom [`PostingSource`]
+    ///
+    /// Combine it with a text query using OpAndMaybe to boost the text matches by the source's weights.
+    pub fn from_posting_source(source: impl IntoPostingSource) -> Result<Self, Error> {
+        source.into_query()
+    }
+
+    /// Construct an OP_SCALE_WEIGHT query, `factor` must not be negative
+    pub fn new_scale_weight(factor: f64, subquery: &Query) -> Result<Self, Error> {
+        Ok(Self {
+            cxxp: ffi::new_query_scale_weight(factor, &subquery.cxxp)?,
+        })
+    }
+
+    /// Scale the weight of this query by `factor` with OP_SCALE_WEIGHT, `a * factor` in C++.
+    /// Fails with `InvalidArgument` if `factor` is negative
+    pub fn scale_weight(&self, factor: f64) -> Result<Self, Error> {
+        Self::new_scale_weight(factor, self)
+    }
+
+    /// Construct an OP_WILDCARD query matching terms which start with `pattern`
+    ///
+    /// `max_expansion` limits how many terms the pattern expands to (0 means no limit), `flags` is
+    /// [`constants::WildcardFlag`] values OR-ed together and `combiner` is the operator used to
+    /// combine the expanded terms, one of OpSynonym, OpOr or OpMax.
+    pub fn new_wildcard(pattern: &str, max_expansion: u32, flags: i32, combiner: constants::XapianOp) -> Result<Self, Error> {
+        Ok(Self {
+            cxxp: ffi::new_query_wildcard(pattern, max_expansion, flags, combiner as i32)?,
+        })
+    }
+
+    /// Construct an OP_EDIT_DISTANCE query matching terms within `edit_distance` of `pattern`
+    ///
+    /// Only the first `min_prefix_len` bytes of `pattern` must match exactly. Only supported by xapian 1.5,
+    /// with 1.4 this returns [`Error::FeatureUnavailable`].
+    pub fn new_edit_distance(
+        pattern: &str,
+        edit_distance: u32,
+        max_expansion: u32,
+        flags: i32,
+        combiner: constants::XapianOp,
+        min_prefix_len: usize,
+    ) -> Result<Self, Error> {
+        Ok(Self {
+            cxxp: ffi::new_query_edit_distance(pattern, edit_distance, max_expansion, flags, combiner as i32, min_prefix_len)?,
+        })
+    }
+
+    /// Combine any number of subqueries with `op`
+    ///
+    /// `parameter` is the window size for OpNear and OpPhrase (0 means the number of subqueries),
+    /// the number of subqueries to pick for OpEliteSet, and is ignored by the other operators.
+    pub fn new_combine(op: constants::XapianOp, subqueries: impl IntoIterator<Item = Query>, parameter: u32) -> Result<Self, Error> {
+        let mut vector = ffi::new_query_vector();
+        for q in subqueries {
+            ffi::query_vector_push(vector.pin_mut(), &q.cxxp);
+        }
+        Ok(Self {
+            cxxp: ffi::new_query_from_vector(op as i32, &vector, parameter)?,
+        })
+    }
+
+    /// Construct an OP_PHRASE query, the subqueries must appear in order within `window` positions
+    pub fn new_phrase(subqueries: impl IntoIterator<Item = Query>, window: u32) -> Result<Self, Error> {
+        Self::new_combine(constants::XapianOp::OpPhrase, subqueries, window)
+    }
+
+    /// Construct an OP_NEAR query, the subqueries must appear in any order within `window` positions
+    pub fn new_near(subqueries: impl IntoIterator<Item = Query>, window: u32) -> Result<Self, Error> {
+        Self::new_combine(constants::XapianOp::OpNear, subqueries, window)
+    }
+
+    /// Construct an OP_ELITE_SET query, which picks the best `set_size` subqueries and ORs them
+    pub fn new_elite_set(subqueries: impl IntoIterator<Item = Query>, set_size: u32) -> Result<Self, Error> {
+        Self::new_combine(constants::XapianOp::OpEliteSet, subqueries, set_size)
+    }
+
+    /// Construct an OP_SYNONYM query, weighting the subqueries as if they were the same term
+    pub fn new_synonym(subqueries: impl IntoIterator<Item = Query>) -> Result<Self, Error> {
+        Self::new_combine(constants::XapianOp::OpSynonym, subqueries, 0)
+    }
+
+    /// Construct an OP_MAX query, using the maximum weight of any matching subquery
+    pub fn new_max(subqueries: impl IntoIterator<Item = Query>) -> Result<Self, Error> {
+        Self::new_combine(constants::XapianOp::OpMax, subqueries, 0)
+    }
+
+    /// Serialise this query to a binary string, which can be turned back into a query with [`Query::unserialise`]
+    pub fn serialise(&self) -> Result<Vec<u8>, Error> {
+        Ok(ffi::query_serialise(&self.cxxp)?)
+    }
+
+    /// Unserialise a query produced by [`Query::serialise`]
+    ///
+    /// `registry` is needed if the query uses custom posting sources, the default registry is used if it is `None`.
+    pub fn unserialise(serialised: &[u8], registry: Option<&Registry>) -> Result<Self, Error> {
+        let cxxp = match registry {
+            Some(registry) => ffi::query_unserialise(serialised, &registry.cxxp)?,
+            None => ffi::query_unserialise(serialised, &Registry::new()?.cxxp)?,
+        };
+        Ok(Self { cxxp })
+    }
+
+    /// Get the type of the top level of the query, an operator or one of the `Leaf*` values
+    pub fn get_type(&self) -> constants::XapianOp {
+        let op = ffi::query_get_type(&self.cxxp);
+        constants::XapianOp::try_from(op).unwrap_or(constants::XapianOp::OpInvalid)
+    }
+
+    /// Get the number of subqueries of the top level of the query
+    pub fn get_num_subqueries(&self) -> usize {
+        ffi::query_get_num_subqueries(&self.cxxp)
+    }
+
+    /// Get the subquery at index `n` of the top level of the query, a `RangeError` if `n` isn't
+    /// less than [`Query::get_num_subqueries`]
+    pub fn get_subquery(&self, n: usize) -> Result<Query, Error> {
+        // xapian indexes the subqueries without checking n
+        let num_subqueries = self.get_num_subqueries();
+        if n >= num_subqueries {
+            return Err(Error::range(format!("subquery {} out of range, the query has {} subqueries", n, num_subqueries)));
+        }
+        Ok(Query {
+            cxxp: ffi::query_get_subquery(&self.cxxp, n)?,
+        })
+    }
+
+    /// Get the within-query frequency of a leaf term query, requires xapian 1.5
+    pub fn get_leaf_wqf(&self) -> Result<u32, Error> {
+        Ok(ffi::query_get_leaf_wqf(&self.cxxp)?)
+    }
+
+    /// Get the query position of a leaf term query (0 means unset), requires xapian 1.5
+    pub fn get_leaf_pos(&self) -> Result<u32, Error> {
+        Ok(ffi::query_get_leaf_pos(&self.cxxp)?)
+    }
+
+    /// Get the length of the query, i.e. the sum of the wqf of its terms
+    pub fn get_length(&self) -> u32 {
+        ffi::query_get_length(&self.cxxp)
+    }
+
+    /// Iterate over the terms in the query, in ascending query position order with duplicates
+    /// at different positions included
+    pub fn get_terms(&self) -> Result<TermIter, Error> {
+        Ok(TermIter::new(ffi::query_get_terms_begin(&self.cxxp)?, ffi::query_get_terms_end(&self.cxxp)?))
+    }
+
+    /// Iterate over the unique terms in the query, in ascending term order
+    pub fn get_unique_terms(&self) -> Result<TermIter, Error> {
+        Ok(TermIter::new(ffi::query_get_unique_terms_begin(&self.cxxp)?, ffi::query_get_unique_terms_end(&self.cxxp)?))
+    }
+
+    /// Build a tree of [`QueryNode`] mirroring the structure of the query, to walk or pattern-match on
+    pub fn to_tree(&self) -> Result<QueryNode, Error> {
+        let node = match self.get_type() {
+            constants::XapianOp::LeafTerm => {
+                let term = self.get_terms()?.next().transpose()?.unwrap_or_default();
+                QueryNode::Term {
+                    term,
+                    wqf: leaf_detail(self.get_leaf_wqf())?,
+                    pos: leaf_detail(self.get_leaf_pos())?,
+                }
+            }
+            constants::XapianOp::LeafPostingSource => QueryNode::PostingSource,
+            constants::XapianOp::LeafMatchAll => QueryNode::MatchAll,
+            constants::XapianOp::LeafMatchNothing => QueryNode::MatchNothing,
+            op => {
+                let subqueries = (0..self.get_num_subqueries())
+                    .map(|n| self.get_subquery(n)?.to_tree())
+                    .collect::<Result<Vec<_>, _>>()?;
+                QueryNode::Op { op, subqueries }
+            }
+        };
+        Ok(node)
+    }
+
     pub fn is_empty(&mut self) -> bool {
         self.cxxp.is_null()
     }
@@ -226,12 +968,143 @@ impl Query {
     }
 }
 
+// Operator overloads matching the binary ones Xapian provides for Xapian::Query in C++,
+// implemented for every combination of owned and borrowed operands. Combining two queries
+// with these operators is always valid, so constructing the result only fails if C++ runs
+// out of memory. Scaling can be given an invalid factor, so it is the fallible
+// Query::scale_weight rather than an operator.
+macro_rules! impl_query_binary_op {
+    ($trait:ident, $method:ident, $op:expr) => {
+        impl ops::$trait<&Query> for &Query {
+            type Output = Query;
+
+            fn $method(self, rhs: &Query) -> Query {
+                self.add_right($op, rhs).expect(concat!("failed to combine queries with ", stringify!($op)))
+            }
+        }
+
+        impl ops::$trait<Query> for &Query {
+            type Output = Query;
+
+            fn $method(self, rhs: Query) -> Query {
+                ops::$trait::$method(self, &rhs)
+            }
+        }
+
+        impl ops::$trait<&Query> for Query {
+            type Output = Query;
+
+            fn $method(self, rhs: &Query) -> Query {
+                ops::$trait::$method(&self, rhs)
+            }
+        }
+
+        impl ops::$trait<Query> for Query {
+            type Output = Query;
+
+            fn $method(self, rhs: Query) -> Query {
+                ops::$trait::$method(&self, &rhs)
+            }
+        }
+    };
+}
+
+impl_query_binary_op!(BitAnd, bitand, constants::XapianOp::OpAnd);
+impl_query_binary_op!(BitOr, bitor, constants::XapianOp::OpOr);
+impl_query_binary_op!(BitXor, bitxor, constants::XapianOp::OpXor);
+// `a - b` is `a & ~b` in C++, i.e. OP_AND_NOT
+impl_query_binary_op!(Sub, sub, constants::XapianOp::OpAndNot);
+
+/// A structural view of a [`Query`], see [`Query::to_tree`]
+#[derive(Debug, Clone, PartialEq)]
+pub enum QueryNode {
+    /// A single term, with its within-query frequency and query position (0 means unset)
+    ///
+    /// `wqf` and `pos` are `None` with xapian 1.4, which doesn't expose them.
+    Term { term: String, wqf: Option<u32>, pos: Option<u32> },
+    /// A query built from a PostingSource
+    PostingSource,
+    /// A query matching all documents
+    MatchAll,
+    /// A query matching no documents
+    MatchNothing,
+    /// An operator applied to its subqueries
+    ///
+    /// Operators whose parameters Xapian doesn't expose, such as OpValueRange or OpWildcard,
+    /// have no subqueries here.
+    Op { op: constants::XapianOp, subqueries: Vec<QueryNode> },
+}
+
+// the leaf accessors need xapian 1.5, without it a tree is still built, just without them
+fn leaf_detail(detail: Result<u32, Error>) -> Result<Option<u32>, Error> {
+    match detail {
+        Ok(detail) => Ok(Some(detail)),
+        Err(Error::FeatureUnavailable(_)) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+impl QueryNode {
+    /// Collect the terms of all the `Term` nodes in this tree, in depth-first order
+    pub fn terms(&self) -> Vec<&str> {
+        let mut terms = Vec::new();
+        self.collect_terms(&mut terms);
+        terms
+    }
+
+    fn collect_terms<'a>(&'a self, terms: &mut Vec<&'a str>) {
+        match self {
+            QueryNode::Term { term, .. } => terms.push(term),
+            QueryNode::Op { subqueries, .. } => subqueries.iter().for_each(|q| q.collect_terms(terms)),
+            _ => {}
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for Query {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let serialised = self.serialise().map_err(serde::ser::Error::custom)?;
+        serializer.serialize_bytes(&serialised)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Query {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct QueryVisitor;
+
+        impl<'de> serde::de::Visitor<'de> for QueryVisitor {
+            type Value = Query;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a serialised xapian query")
+            }
+
+            fn visit_bytes<E: serde::de::Error>(self, v: &[u8]) -> Result<Query, E> {
+                Query::unserialise(v, None).map_err(E::custom)
+            }
+
+            // formats without a native bytes type, e.g. json, write bytes as a sequence
+            fn visit_seq<A: serde::de::SeqAccess<'de>>(self, mut seq: A) -> Result<Query, A::Error> {
+                let mut bytes = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+                while let Some(b) = seq.next_element::<u8>()? {
+                    bytes.push(b);
+                }
+                self.visit_bytes(&bytes)
+            }
+        }
+
+        deserializer.deserialize_bytes(QueryVisitor)
+    }
+}
+
 pub struct QueryParser {
     pub cxxp: UniquePtr<ffi::QueryParser>,
 }
 
 impl QueryParser {
-    pub fn new() -> Result<Self, cxx::Exception> {
+    pub fn new() -> Result<Self, Error> {
         unsafe {
             Ok(Self {
                 cxxp: ffi::new_query_parser()?,
@@ -239,44 +1112,43 @@ impl QueryParser {
         }
     }
 
-    pub fn set_max_wildcard_expansion(&mut self, limit: i32) -> Result<(), cxx::Exception> {
+    pub fn set_max_wildcard_expansion(&mut self, limit: i32) -> Result<(), Error> {
         unsafe {
             ffi::set_max_wildcard_expansion(self.cxxp.pin_mut(), limit)?;
             Ok(())
         }
     }
 
-    pub fn set_stemmer(&mut self, mut stem: Stem) -> Result<(), cxx::Exception> {
+    pub fn set_stemmer(&mut self, mut stem: Stem) -> Result<(), Error> {
         ffi::set_stemmer_to_qp(self.cxxp.pin_mut(), stem.cxxp.pin_mut())?;
         Ok(())
     }
 
-    pub fn set_database(&mut self, database: &mut Database) -> Result<(), cxx::Exception> {
+    pub fn set_database(&mut self, database: &mut Database) -> Result<(), Error> {
         ffi::set_database(self.cxxp.pin_mut(), database.cxxp.pin_mut())?;
         Ok(())
     }
 
-    pub fn add_prefix(&mut self, field: &str, prefix: &str) -> Result<(), cxx::Exception> {
-        ffi::add_prefix(self.cxxp.pin_mut(), field, prefix)?;
-        Ok(())
-    }
-
-    pub fn add_boolean_prefix(&mut self, field: &str, prefix: &str) -> Result<(), cxx::Exception> {
-        ffi::add_boolean_prefix(self.cxxp.pin_mut(), field, prefix)?;
-        Ok(())
+    /// Map `field` to a term prefix, or to a [`FieldProcessor`] which the query parser takes ownership of
+    pub fn add_prefix(&mut self, field: &str, prefix: impl FieldPrefix) -> Result<(), Error> {
+        prefix.add_prefix_to(self, field)
     }
 
-    pub fn add_rangeprocessor(&mut self, range_proc: &mut RangeProcessor) -> Result<(), cxx::Exception> {
-        ffi::add_rangeprocessor(self.cxxp.pin_mut(), range_proc.cxxp.pin_mut())?;
-        Ok(())
+    /// Map `field` to a boolean filter term prefix, or to a [`FieldProcessor`] which the query parser takes ownership of
+    pub fn add_boolean_prefix(&mut self, field: &str, prefix: impl FieldPrefix) -> Result<(), Error> {
+        prefix.add_boolean_prefix_to(self, field)
     }
 
-    pub fn add_number_rangeprocessor(&mut self, range_proc: &mut NumberRangeProcessor) -> Result<(), cxx::Exception> {
-        ffi::add_number_rangeprocessor(self.cxxp.pin_mut(), range_proc.cxxp.pin_mut())?;
-        Ok(())
+    /// Add a range processor, one of the built-in ones such as [`NumberRangeProcessor`] or a
+    /// [`RangeProcessor`] implemented in rust. The query parser takes ownership of it.
+    ///
+    /// Ranges handled by processors with the same `grouping` are combined with `OP_OR`, other
+    /// ranges are combined with `OP_AND`. With no grouping every range is combined with `OP_AND`.
+    pub fn add_rangeprocessor(&mut self, range_proc: impl IntoRangeProcessor, grouping: Option<&str>) -> Result<(), Error> {
+        range_proc.add_to(self, grouping)
     }
 
-    pub fn parse_query(&mut self, query: &str, flags: i32) -> Result<Query, cxx::Exception> {
+    pub fn parse_query(&mut self, query: &str, flags: i32) -> Result<Query, Error> {
         unsafe {
             Ok(Query {
                 cxxp: ffi::parse_query(self.cxxp.pin_mut(), query, flags)?,
@@ -284,11 +1156,159 @@ impl QueryParser {
         }
     }
 
-    pub fn parse_query_with_prefix(&mut self, query: &str, flags: i32, prefix: &str) -> Result<Query, cxx::Exception> {
+    pub fn parse_query_with_prefix(&mut self, query: &str, flags: i32, prefix: &str) -> Result<Query, Error> {
         Ok(Query {
             cxxp: ffi::parse_query_with_prefix(self.cxxp.pin_mut(), query, flags, prefix)?,
         })
     }
+
+    /// Get the spelling-corrected query string from the last call to `parse_query`.
+    ///
+    /// Only set when the query was parsed with `FLAG_SPELLING_CORRECTION` and a database
+    /// has been set with [`QueryParser::set_database`], empty if no correction was made.
+    pub fn get_corrected_query_string(&mut self) -> Result<String, Error> {
+        Ok(ffi::query_parser_get_corrected_query_string(self.cxxp.pin_mut())?)
+    }
+
+    /// Set the stopper used to ignore stop words in query strings, the query parser takes ownership of it
+    pub fn set_stopper(&mut self, stopper: impl Into<Stopper>) -> Result<(), Error> {
+        ffi::query_parser_set_stopper(self.cxxp.pin_mut(), stopper.into().cxxp)?;
+        Ok(())
+    }
+
+    /// Iterate over the stop words which were ignored by the last call to `parse_query`
+    pub fn stoplist(&mut self) -> Result<TermIter, Error> {
+        Ok(TermIter::new(ffi::query_parser_stoplist_begin(self.cxxp.pin_mut())?, ffi::query_parser_stoplist_end(self.cxxp.pin_mut())?))
+    }
+}
+
+/// Turns the text of a field into a query, for field syntax the query parser doesn't know,
+/// see [`QueryParser::add_prefix`]
+pub trait FieldProcessor {
+    /// Called with the text following `field:` in the query string
+    fn process(&mut self, term: &str) -> Result<Query, Error>;
+}
+
+/// What a field can be mapped to by [`QueryParser::add_prefix`] and [`QueryParser::add_boolean_prefix`],
+/// either a term prefix string or a [`FieldProcessor`]
+pub trait FieldPrefix {
+    #[doc(hidden)]
+    fn add_prefix_to(self, qp: &mut QueryParser, field: &str) -> Result<(), Error>;
+    #[doc(hidden)]
+    fn add_boolean_prefix_to(self, qp: &mut QueryParser, field: &str) -> Result<(), Error>;
+}
+
+impl FieldPrefix for &str {
+    fn add_prefix_to(self, qp: &mut QueryParser, field: &str) -> Result<(), Error> {
+        ffi::add_prefix(qp.cxxp.pin_mut(), field, self)?;
+        Ok(())
+    }
+
+    fn add_boolean_prefix_to(self, qp: &mut QueryParser, field: &str) -> Result<(), Error> {
+        ffi::add_boolean_prefix(qp.cxxp.pin_mut(), field, self)?;
+        Ok(())
+    }
+}
+
+impl FieldPrefix for String {
+    fn add_prefix_to(self, qp: &mut QueryParser, field: &str) -> Result<(), Error> {
+        self.as_str().add_prefix_to(qp, field)
+    }
+
+    fn add_boolean_prefix_to(self, qp: &mut QueryParser, field: &str) -> Result<(), Error> {
+        self.as_str().add_boolean_prefix_to(qp, field)
+    }
+}
+
+impl FieldPrefix for &String {
+    fn add_prefix_to(self, qp: &mut QueryParser, field: &str) -> Result<(), Error> {
+        self.as_str().add_prefix_to(qp, field)
+    }
+
+    fn add_boolean_prefix_to(self, qp: &mut QueryParser, field: &str) -> Result<(), Error> {
+        self.as_str().add_boolean_prefix_to(qp, field)
+    }
+}
+
+// the processor is moved into a C++ FieldProcessor which is released to the query parser,
+// so it lives as long as the parser does
+impl<T: FieldProcessor + 'static> FieldPrefix for T {
+    fn add_prefix_to(self, qp: &mut QueryParser, field: &str) -> Result<(), Error> {
+        let callback = Box::new(FieldProcessorCallback { processor: Box::new(self) });
+        ffi::query_parser_add_prefix_processor(qp.cxxp.pin_mut(), field, ffi::new_rust_field_processor(callback)?)?;
+        Ok(())
+    }
+
+    fn add_boolean_prefix_to(self, qp: &mut QueryParser, field: &str) -> Result<(), Error> {
+        let callback = Box::new(FieldProcessorCallback { processor: Box::new(self) });
+        ffi::query_parser_add_boolean_prefix_processor(qp.cxxp.pin_mut(), field, ffi::new_rust_field_processor(callback)?)?;
+        Ok(())
+    }
+}
+
+struct FieldProcessorCallback {
+    processor: Box<dyn FieldProcessor>,
+}
+
+fn field_processor_process(callback: &mut FieldProcessorCallback, term: &str) -> Result<UniquePtr<ffi::Query>, String> {
+    match catch_callback_panic("FieldProcessor", || callback.processor.process(term))? {
+        Ok(query) => Ok(query.cxxp),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+/// Parses ranges such as `price:$5..$20` into a query, see [`QueryParser::add_rangeprocessor`]
+pub trait RangeProcessor {
+    /// Handle the range `begin..end`, with the prefix or suffix already removed. Either end may be empty
+    /// for an open ended range.
+    ///
+    /// Returns `None` if this isn't a range this processor understands, so the next processor is tried.
+    fn check_range(&mut self, begin: &str, end: &str) -> Result<Option<Query>, Error>;
+
+    /// The string which marks a range as belonging to this processor, empty to try every range
+    fn prefix(&self) -> &str {
+        ""
+    }
+
+    /// Whether [`RangeProcessor::prefix`] is a prefix or a suffix of the range
+    fn flags(&self) -> constants::RangeProcessorFlags {
+        constants::RangeProcessorFlags::RP_PREFIX
+    }
+}
+
+/// What can be added with [`QueryParser::add_rangeprocessor`], implemented for the built-in
+/// range processors and for every [`RangeProcessor`]
+pub trait IntoRangeProcessor {
+    #[doc(hidden)]
+    fn add_to(self, qp: &mut QueryParser, grouping: Option<&str>) -> Result<(), Error>;
+}
+
+impl<T: RangeProcessor + 'static> IntoRangeProcessor for T {
+    fn add_to(self, qp: &mut QueryParser, grouping: Option<&str>) -> Result<(), Error> {
+        let prefix = self.prefix().to_string();
+        let flags = self.flags();
+        let callback = Box::new(RangeProcessorCallback { processor: Box::new(self) });
+        ffi::query_parser_add_rangeprocessor(
+            qp.cxxp.pin_mut(),
+            ffi::new_rust_range_processor(&prefix, flags.bits(), callback)?,
+            grouping.is_some(),
+            grouping.unwrap_or(""),
+        )?;
+        Ok(())
+    }
+}
+
+struct RangeProcessorCallback {
+    processor: Box<dyn RangeProcessor>,
+}
+
+// a null query tells the C++ side the range wasn't handled
+fn range_processor_check_range(callback: &mut RangeProcessorCallback, begin: &str, end: &str) -> Result<UniquePtr<ffi::Query>, String> {
+    match catch_callback_panic("RangeProcessor", || callback.processor.check_range(begin, end))? {
+        Ok(Some(query)) => Ok(query.cxxp),
+        Ok(None) => Ok(UniquePtr::null()),
+        Err(e) => Err(e.to_string()),
+    }
 }
 
 pub struct MSetIterator {
@@ -298,7 +1318,7 @@ pub struct MSetIterator {
 }
 
 impl MSetIterator {
-    // pub fn is_next(&mut self) -> Result<bool, cxx::Exception> {
+    // pub fn is_next(&mut self) -> Result<bool, Error> {
     //     #[allow(unused_unsafe)]
     //     unsafe {
     //         let mut err = 0;
@@ -312,7 +1332,7 @@ impl MSetIterator {
     //     }
     // }
 
-    // pub fn next(&mut self) -> Result<(), cxx::Exception> {
+    // pub fn next(&mut self) -> Result<(), Error> {
     //     #[allow(unused_unsafe)]
     //     unsafe {
     //         let mut err = 0;
@@ -328,21 +1348,93 @@ impl MSetIterator {
     //     }
     // }
 
-    pub fn get_document(&mut self) -> Result<Document, cxx::Exception> {
+    pub fn get_document(&mut self) -> Result<Document, Error> {
         Ok(Document {
             cxxp: ffi::mset_iterator_get_document(self.cxxp.pin_mut())?,
         })
     }
 
-    pub fn eq(&mut self, other: &mut MSetIterator) -> Result<bool, cxx::Exception> {
+    pub fn eq(&mut self, other: &mut MSetIterator) -> Result<bool, Error> {
         Ok(ffi::mset_iterator_eq(self.cxxp.pin_mut(), other.cxxp.pin_mut())?)
     }
 
-    pub fn next(&mut self) -> Result<(), cxx::Exception> {
+    pub fn next(&mut self) -> Result<(), Error> {
         ffi::mset_iterator_next(self.cxxp.pin_mut())?;
 
         Ok(())
     }
+
+    /// The accessors return a `RangeError` when the iterator isn't on an item, e.g. at [`MSet::end`]
+    pub fn get_docid(&mut self) -> Result<u32, Error> {
+        Ok(ffi::mset_iterator_get_docid(self.cxxp.pin_mut())?)
+    }
+
+    /// Get the rank of the document, 0 for the first item in the MSet
+    pub fn get_rank(&mut self) -> Result<u32, Error> {
+        Ok(ffi::mset_iterator_get_rank(self.cxxp.pin_mut())?)
+    }
+
+    pub fn get_weight(&mut self) -> Result<f64, Error> {
+        Ok(ffi::mset_iterator_get_weight(self.cxxp.pin_mut())?)
+    }
+
+    /// Convert the weight of the document to a percentage score
+    pub fn get_percent(&mut self) -> Result<i32, Error> {
+        Ok(ffi::mset_iterator_get_percent(self.cxxp.pin_mut())?)
+    }
+
+    /// Return an estimate of the number of documents collapsed into this one
+    pub fn get_collapse_count(&mut self) -> Result<u32, Error> {
+        Ok(ffi::mset_iterator_get_collapse_count(self.cxxp.pin_mut())?)
+    }
+
+    /// Return the collapse key of the document, empty if no collapse key was set
+    pub fn get_collapse_key(&mut self) -> Result<Vec<u8>, Error> {
+        Ok(ffi::mset_iterator_get_collapse_key(self.cxxp.pin_mut())?)
+    }
+}
+
+/// A single hit in an [`MSet`], as yielded by iterating over it
+pub struct Match {
+    pub docid: u32,
+    pub rank: u32,
+    pub weight: f64,
+    pub percent: i32,
+    pub collapse_count: u32,
+    pub collapse_key: Vec<u8>,
+    iter: MSetIterator,
+}
+
+impl Match {
+    fn new(mut iter: MSetIterator) -> Result<Self, Error> {
+        Ok(Self {
+            docid: iter.get_docid()?,
+            rank: iter.get_rank()?,
+            weight: iter.get_weight()?,
+            percent: iter.get_percent()?,
+            collapse_count: iter.get_collapse_count()?,
+            collapse_key: iter.get_collapse_key()?,
+            iter,
+        })
+    }
+
+    /// Fetch the document of this hit, it is only read from the database when called
+    pub fn get_document(&mut self) -> Result<Document, Error> {
+        self.iter.get_document()
+    }
+}
+
+impl fmt::Debug for Match {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Match")
+            .field("docid", &self.docid)
+            .field("rank", &self.rank)
+            .field("weight", &self.weight)
+            .field("percent", &self.percent)
+            .field("collapse_count", &self.collapse_count)
+            .field("collapse_key", &self.collapse_key)
+            .finish()
+    }
 }
 
 pub struct MSet {
@@ -359,29 +1451,29 @@ impl MSet {
     // }
 
     // https://xapian.org/docs/sourcedoc/html/classXapian_1_1MSet.html#ad00d5e7f564fe0e5031cb5f89b829ffe
-    pub fn begin(&mut self) -> Result<MSetIterator, cxx::Exception> {
+    pub fn begin(&mut self) -> Result<MSetIterator, Error> {
         Ok(MSetIterator {
             cxxp: ffi::mset_begin(self.cxxp.pin_mut())?,
         })
     }
 
-    pub fn end(&mut self) -> Result<MSetIterator, cxx::Exception> {
+    pub fn end(&mut self) -> Result<MSetIterator, Error> {
         Ok(MSetIterator {
             cxxp: ffi::mset_end(self.cxxp.pin_mut())?,
         })
     }
 
-    pub fn back(&mut self) -> Result<MSetIterator, cxx::Exception> {
+    pub fn back(&mut self) -> Result<MSetIterator, Error> {
         Ok(MSetIterator {
             cxxp: ffi::mset_back(self.cxxp.pin_mut())?,
         })
     }
 
-    pub fn get_matches_estimated(&mut self) -> Result<i32, cxx::Exception> {
+    pub fn get_matches_estimated(&mut self) -> Result<i32, Error> {
         Ok(ffi::get_matches_estimated(self.cxxp.pin_mut())?)
     }
 
-    pub fn size(&mut self) -> Result<i32, cxx::Exception> {
+    pub fn size(&mut self) -> Result<i32, Error> {
         Ok(ffi::mset_size(self.cxxp.pin_mut())?)
     }
 
@@ -389,74 +1481,472 @@ impl MSet {
         let res = ffi::mset_snippet(self.cxxp.pin_mut(), text, length, stem.cxxp.pin_mut(), flags, hi_start, hi_end, omit);
         return res.to_string();
     }
-}
 
-pub struct Enquire {
-    pub cxxp: UniquePtr<ffi::Enquire>,
-    sorter: Option<MultiValueKeyMaker>,
+    /// Iterate over the hits in this MSet
+    pub fn iter(&self) -> MSetIter {
+        self.into_iter()
+    }
 }
 
-impl Enquire {
-    pub fn get_mset(&mut self, from: i32, size: i32) -> Result<MSet, cxx::Exception> {
-        Ok(MSet {
-            cxxp: ffi::get_mset(self.cxxp.pin_mut(), from, size)?,
-        })
+impl Clone for MSet {
+    /// Xapian::MSet is a reference counted handle, so cloning is cheap
+    fn clone(&self) -> Self {
+        Self { cxxp: ffi::mset_clone(&self.cxxp) }
     }
+}
 
-    pub fn set_query(&mut self, query: &mut Query) -> Result<(), cxx::Exception> {
-        ffi::set_query(self.cxxp.pin_mut(), query.cxxp.pin_mut())?;
-        Ok(())
+/// Iterator over the hits of an [`MSet`]
+///
+/// A hit which can't be read is yielded as an error, as is a failure to get the size of the MSet.
+pub struct MSetIter {
+    mset: MSet,
+    index: u32,
+    size: u32,
+    size_error: Option<Error>,
+}
+
+impl MSetIter {
+    fn new(mut mset: MSet) -> Self {
+        let (size, size_error) = match mset.size() {
+            Ok(size) => (size as u32, None),
+            Err(e) => (0, Some(e)),
+        };
+        Self { mset, index: 0, size, size_error }
     }
+}
 
-    pub fn set_sort_by_key(&mut self, mut sorter: MultiValueKeyMaker, reverse: bool) -> Result<(), cxx::Exception> {
-        ffi::set_sort_by_key(self.cxxp.pin_mut(), sorter.cxxp.pin_mut(), reverse)?;
-        self.sorter = Some(sorter);
-        Ok(())
+impl Iterator for MSetIter {
+    type Item = Result<Match, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(e) = self.size_error.take() {
+            return Some(Err(e));
+        }
+        if self.index >= self.size {
+            return None;
+        }
+        let index = self.index;
+        self.index += 1;
+        Some(ffi::mset_iterator_at(self.mset.cxxp.pin_mut(), index).map_err(Error::from).and_then(|cxxp| Match::new(MSetIterator { cxxp })))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = (self.size - self.index) as usize + self.size_error.is_some() as usize;
+        (remaining, Some(remaining))
+    }
+}
+
+impl ExactSizeIterator for MSetIter {}
+
+impl IntoIterator for MSet {
+    type Item = Result<Match, Error>;
+    type IntoIter = MSetIter;
+
+    fn into_iter(self) -> MSetIter {
+        MSetIter::new(self)
+    }
+}
+
+impl IntoIterator for &MSet {
+    type Item = Result<Match, Error>;
+    type IntoIter = MSetIter;
+
+    fn into_iter(self) -> MSetIter {
+        MSetIter::new(self.clone())
     }
+}
+
+pub struct Enquire {
+    pub cxxp: UniquePtr<ffi::Enquire>,
+    sorter: Option<Box<dyn KeyMaker>>,
+    // kept alive for as long as the enquire may call them
+    matchspies: Vec<UniquePtr<ffi::MatchSpy>>,
+    // with the address C++ was given, which must stay the one the spy owns
+    value_count_spies: Vec<(Rc<RefCell<ValueCountMatchSpy>>, *const ffi::ValueCountMatchSpy)>,
+}
 
-    pub fn add_matchspy() {
-        unimplemented!()
+/// Mutably borrow every registered [`ValueCountMatchSpy`] for the duration of a match, as C++ writes
+/// to them, and check none of them has been replaced since it was handed to the enquire.
+fn borrow_value_count_spies(
+    spies: &[(Rc<RefCell<ValueCountMatchSpy>>, *const ffi::ValueCountMatchSpy)],
+) -> Result<Vec<RefMut<'_, ValueCountMatchSpy>>, Error> {
+    spies
+        .iter()
+        .map(|(vcms, registered)| {
+            let Ok(spy) = vcms.try_borrow_mut() else {
+                return Err(Error::invalid_operation("ValueCountMatchSpy is borrowed while matching".to_string()));
+            };
+            if spy.as_ptr() != *registered {
+                return Err(Error::invalid_operation("ValueCountMatchSpy was replaced after being added".to_string()));
+            }
+            Ok(spy)
+        })
+        .collect()
+}
+
+impl Enquire {
+    pub fn get_mset(&mut self, from: i32, size: i32) -> Result<MSet, Error> {
+        let _spies = borrow_value_count_spies(&self.value_count_spies)?;
+        Ok(MSet {
+            cxxp: ffi::get_mset(self.cxxp.pin_mut(), from, size)?,
+        })
     }
 
-    pub fn add_matchspy_value_count(&mut self, vcms: &mut ValueCountMatchSpy) -> Result<(), cxx::Exception> {
-        ffi::add_matchspy_value_count(self.cxxp.pin_mut(), vcms.cxxp.pin_mut())?;
+    pub fn set_query(&mut self, query: &mut Query) -> Result<(), Error> {
+        ffi::set_query(self.cxxp.pin_mut(), query.cxxp.pin_mut())?;
         Ok(())
     }
 
-    pub fn set_weighting_scheme_bool(&mut self, bool_weight: &mut BoolWeight) -> Result<(), cxx::Exception> {
-        ffi::enquire_set_weighting_scheme_bool(self.cxxp.pin_mut(), bool_weight.cxxp.pin_mut())?;
+    /// Sort the results by the keys `sorter` generates, the enquire keeps it for as long as it is used
+    pub fn set_sort_by_key(&mut self, sorter: impl KeyMaker, reverse: bool) -> Result<(), Error> {
+        sorter.set_on(self, reverse)
+    }
+
+    /// Add a [`MatchSpy`] which is called for each candidate document during matching.
+    ///
+    /// The enquire keeps a clone of `spy`, keep another one to read the results after [`Enquire::get_mset`].
+    /// A panic in the spy, or `spy` being borrowed while matching, aborts the match and is returned as an error.
+    pub fn add_matchspy<T: MatchSpy + 'static>(&mut self, spy: &Rc<RefCell<T>>) -> Result<(), Error> {
+        let callback = Box::new(MatchSpyCallback { spy: spy.clone() });
+        let mut cxxp = ffi::new_rust_match_spy(callback)?;
+        ffi::enquire_add_matchspy(self.cxxp.pin_mut(), cxxp.pin_mut())?;
+        self.matchspies.push(cxxp);
         Ok(())
     }
 
-    pub fn set_weighting_scheme_bm25(&mut self, bm25_weight: &mut BM25Weight) -> Result<(), cxx::Exception> {
-        ffi::enquire_set_weighting_scheme_bm25(self.cxxp.pin_mut(), bm25_weight.cxxp.pin_mut())?;
+    /// Add a [`ValueCountMatchSpy`] to count the values in its slot across the candidate documents.
+    ///
+    /// The enquire keeps a clone of `vcms`, keep another one to read the counts after [`Enquire::get_mset`].
+    /// Fails with `InvalidOperation` if `vcms` is currently borrowed. Matching fails the same way
+    /// while `vcms` is borrowed, or if it has been replaced with another spy since it was added.
+    pub fn add_matchspy_value_count(&mut self, vcms: &Rc<RefCell<ValueCountMatchSpy>>) -> Result<(), Error> {
+        let Ok(mut spy) = vcms.try_borrow_mut() else {
+            return Err(Error::invalid_operation("ValueCountMatchSpy is already borrowed".to_string()));
+        };
+        ffi::add_matchspy_value_count(self.cxxp.pin_mut(), spy.cxxp.pin_mut())?;
+        let registered = spy.as_ptr();
+        drop(spy);
+        self.value_count_spies.push((vcms.clone(), registered));
         Ok(())
     }
 
-    pub fn set_docid_order(&mut self, docid_order: constants::EnquireDocidOrder) -> Result<(), cxx::Exception> {
+    /// Set the weighting scheme used for the match, one of the built-in schemes such as [`BM25Weight`]
+    /// or a custom [`Weight`] implemented in rust
+    pub fn set_weighting_scheme(&mut self, scheme: impl WeightScheme) -> Result<(), Error> {
+        scheme.set_on(self)
+    }
+
+    pub fn set_docid_order(&mut self, docid_order: constants::EnquireDocidOrder) -> Result<(), Error> {
         ffi::enquire_set_docid_order(self.cxxp.pin_mut(), docid_order as i32)?;
         Ok(())
     }
 
-    pub fn set_sort_by_relevance(&mut self) -> Result<(), cxx::Exception> {
+    pub fn set_sort_by_relevance(&mut self) -> Result<(), Error> {
         ffi::enquire_set_sort_by_relevance(self.cxxp.pin_mut())?;
         Ok(())
     }
 
-    pub fn set_sort_by_value(&mut self, sort_key: u32, reverse: bool) -> Result<(), cxx::Exception> {
+    pub fn set_sort_by_value(&mut self, sort_key: u32, reverse: bool) -> Result<(), Error> {
         ffi::enquire_set_sort_by_value(self.cxxp.pin_mut(), sort_key, reverse)?;
         Ok(())
     }
 
-    pub fn set_sort_by_relevance_then_value(&mut self, sort_key: u32, reverse: bool) -> Result<(), cxx::Exception> {
+    pub fn set_sort_by_relevance_then_value(&mut self, sort_key: u32, reverse: bool) -> Result<(), Error> {
         ffi::enquire_set_sort_by_relevance_then_value(self.cxxp.pin_mut(), sort_key, reverse)?;
         Ok(())
     }
 
-    pub fn set_collapse_key(&mut self, collapse_key: u32, collapse_max: u32)-> Result<(), cxx::Exception> {
+    pub fn set_collapse_key(&mut self, collapse_key: u32, collapse_max: u32)-> Result<(), Error> {
         ffi::enquire_set_collapse_key(self.cxxp.pin_mut(), collapse_key, collapse_max)?;
         Ok(())
     }
+
+    /// Get the MSet, using the documents in `rset` for relevance feedback
+    pub fn get_mset_with_rset(&mut self, from: u32, size: u32, rset: &mut RSet) -> Result<MSet, Error> {
+        let _spies = borrow_value_count_spies(&self.value_count_spies)?;
+        Ok(MSet {
+            cxxp: ffi::enquire_get_mset_with_rset(self.cxxp.pin_mut(), from, size, rset.cxxp.pin_mut())?,
+        })
+    }
+
+    /// Get the match set for the current query, only including documents accepted by `decider`
+    ///
+    /// At least `checkatleast` documents are considered, which improves the match count estimates.
+    /// A panic in `decider` aborts the match and is returned as an error.
+    pub fn get_mset_with_decider(&mut self, from: u32, size: u32, checkatleast: u32, decider: &MatchDecider) -> Result<MSet, Error> {
+        let _spies = borrow_value_count_spies(&self.value_count_spies)?;
+        Ok(MSet {
+            cxxp: ffi::enquire_get_mset_with_decider(self.cxxp.pin_mut(), from, size, checkatleast, decider)?,
+        })
+    }
+
+    /// Get the expand set for the current query, i.e. the terms which best describe the documents in `rset`
+    ///
+    /// `flags` is zero or more of [`constants::ESetFlag`] OR-ed together. Only terms accepted by
+    /// `decider` (if any) and with a weight of at least `min_wt` are returned.
+    pub fn get_eset(&mut self, maxitems: u32, rset: &mut RSet, flags: i32, decider: Option<&ExpandDecider>, min_wt: f64) -> Result<ESet, Error> {
+        let cxxp = match decider {
+            Some(decider) => ffi::enquire_get_eset_with_decider(self.cxxp.pin_mut(), maxitems, rset.cxxp.pin_mut(), flags, decider, min_wt)?,
+            None => ffi::enquire_get_eset(self.cxxp.pin_mut(), maxitems, rset.cxxp.pin_mut(), flags, min_wt)?,
+        };
+        Ok(ESet { cxxp })
+    }
+}
+
+type MatchDeciderFn = Box<dyn FnMut(&mut Document) -> bool>;
+
+/// Decides which documents are included in an [`MSet`], see [`Enquire::get_mset_with_decider`]
+pub struct MatchDecider {
+    // xapian calls the decider through a const reference
+    accept: RefCell<MatchDeciderFn>,
+}
+
+impl MatchDecider {
+    /// `accept` is called for each candidate document and returns whether to include it
+    pub fn new(accept: impl FnMut(&mut Document) -> bool + 'static) -> Self {
+        Self {
+            accept: RefCell::new(Box::new(accept)),
+        }
+    }
+}
+
+fn match_decider_accept(decider: &MatchDecider, doc: UniquePtr<ffi::Document>) -> Result<bool, String> {
+    let Ok(mut accept) = decider.accept.try_borrow_mut() else {
+        return Err("MatchDecider called while it is already running".to_string());
+    };
+    let mut doc = Document { cxxp: doc };
+    catch_callback_panic("MatchDecider", || accept(&mut doc))
+}
+
+/// Runs a rust callback on behalf of C++, turning a panic in it into an error
+///
+/// A panic must not unwind into C++, so every extern "Rust" callback runs through this and returns
+/// `Result<_, String>`. cxx throws the error in C++ as `rust::Error`, which unwinds through Xapian
+/// and comes out of the call which ran the callback, e.g. [`Enquire::get_mset`], as an [`Error`].
+fn catch_callback_panic<T>(callback: &str, f: impl FnOnce() -> T) -> Result<T, String> {
+    panic::catch_unwind(panic::AssertUnwindSafe(f)).map_err(|payload| format!("{} panicked: {}", callback, panic_message(payload)))
+}
+
+fn panic_message(payload: Box<dyn Any + Send>) -> String {
+    payload
+        .downcast_ref::<&str>()
+        .map(|s| s.to_string())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "unknown panic".to_string())
+}
+
+/// Observes the documents considered during matching, see [`Enquire::add_matchspy`]
+pub trait MatchSpy {
+    /// Called for each candidate document with its weight
+    fn observe(&mut self, doc: &mut Document, wt: f64);
+}
+
+struct MatchSpyCallback {
+    spy: Rc<RefCell<dyn MatchSpy>>,
+}
+
+fn match_spy_observe(callback: &mut MatchSpyCallback, doc: UniquePtr<ffi::Document>, wt: f64) -> Result<(), String> {
+    let Ok(mut spy) = callback.spy.try_borrow_mut() else {
+        return Err("MatchSpy is already borrowed while matching".to_string());
+    };
+    let mut doc = Document { cxxp: doc };
+    catch_callback_panic("MatchSpy", || spy.observe(&mut doc, wt))
+}
+
+/// The documents marked as relevant, used for relevance feedback and query expansion
+pub struct RSet {
+    pub cxxp: UniquePtr<ffi::RSet>,
+}
+
+impl RSet {
+    pub fn new() -> Result<Self, Error> {
+        Ok(Self { cxxp: ffi::new_rset()? })
+    }
+
+    /// Mark a document as relevant
+    pub fn add_document(&mut self, docid: u32) -> Result<(), Error> {
+        ffi::rset_add_document(self.cxxp.pin_mut(), docid)?;
+        Ok(())
+    }
+
+    /// Unmark a document as relevant
+    pub fn remove_document(&mut self, docid: u32) -> Result<(), Error> {
+        ffi::rset_remove_document(self.cxxp.pin_mut(), docid)?;
+        Ok(())
+    }
+
+    /// Check if a document is marked as relevant
+    pub fn contains(&mut self, docid: u32) -> Result<bool, Error> {
+        Ok(ffi::rset_contains(self.cxxp.pin_mut(), docid)?)
+    }
+
+    pub fn size(&mut self) -> Result<u32, Error> {
+        Ok(ffi::rset_size(self.cxxp.pin_mut())?)
+    }
+
+    pub fn empty(&mut self) -> Result<bool, Error> {
+        Ok(ffi::rset_empty(self.cxxp.pin_mut())?)
+    }
+}
+
+/// Decides which terms are included in an [`ESet`], see [`Enquire::get_eset`]
+pub struct ExpandDecider {
+    accept: Box<dyn Fn(&str) -> bool>,
+}
+
+impl ExpandDecider {
+    /// `accept` is called for each candidate term and returns whether to include it,
+    /// terms which aren't valid UTF-8 are never included
+    pub fn new(accept: impl Fn(&str) -> bool + 'static) -> Self {
+        Self { accept: Box::new(accept) }
+    }
+}
+
+fn expand_decider_accept(decider: &ExpandDecider, term: &[u8]) -> Result<bool, String> {
+    let Ok(term) = std::str::from_utf8(term) else {
+        return Ok(false);
+    };
+    catch_callback_panic("ExpandDecider", || (decider.accept)(term))
+}
+
+/// A term suggested by query expansion, as yielded by iterating over an [`ESet`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExpandTerm {
+    pub term: String,
+    pub weight: f64,
+}
+
+/// The set of terms suggested by query expansion
+pub struct ESet {
+    pub cxxp: UniquePtr<ffi::ESet>,
+}
+
+impl ESet {
+    /// Return number of items in this ESet
+    pub fn size(&mut self) -> Result<u32, Error> {
+        Ok(ffi::eset_size(self.cxxp.pin_mut())?)
+    }
+
+    /// Return a bound on the full size of this ESet
+    pub fn get_ebound(&mut self) -> Result<u32, Error> {
+        Ok(ffi::eset_get_ebound(self.cxxp.pin_mut())?)
+    }
+
+    pub fn begin(&mut self) -> Result<ESetIterator, Error> {
+        Ok(ESetIterator {
+            cxxp: ffi::eset_begin(self.cxxp.pin_mut())?,
+        })
+    }
+
+    pub fn end(&mut self) -> Result<ESetIterator, Error> {
+        Ok(ESetIterator {
+            cxxp: ffi::eset_end(self.cxxp.pin_mut())?,
+        })
+    }
+
+    /// Iterate over the expand terms in this ESet
+    pub fn iter(&self) -> ESetIter {
+        self.into_iter()
+    }
+}
+
+impl Clone for ESet {
+    /// Xapian::ESet is a reference counted handle, so cloning is cheap
+    fn clone(&self) -> Self {
+        Self { cxxp: ffi::eset_clone(&self.cxxp) }
+    }
+}
+
+/// Iterator over the terms of an [`ESet`]
+pub struct ESetIter {
+    eset: ESet,
+    index: u32,
+    size: u32,
+    size_error: Option<Error>,
+}
+
+impl ESetIter {
+    fn new(mut eset: ESet) -> Self {
+        let (size, size_error) = match eset.size() {
+            Ok(size) => (size, None),
+            Err(e) => (0, Some(e)),
+        };
+        Self { eset, index: 0, size, size_error }
+    }
+}
+
+impl Iterator for ESetIter {
+    type Item = Result<ExpandTerm, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(e) = self.size_error.take() {
+            return Some(Err(e));
+        }
+        if self.index >= self.size {
+            return None;
+        }
+        let index = self.index;
+        self.index += 1;
+        Some(ffi::eset_iterator_at(self.eset.cxxp.pin_mut(), index).map_err(Error::from).and_then(|cxxp| {
+            let mut iter = ESetIterator { cxxp };
+            Ok(ExpandTerm {
+                term: iter.get_term()?,
+                weight: iter.get_weight()?,
+            })
+        }))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = (self.size - self.index) as usize + self.size_error.is_some() as usize;
+        (remaining, Some(remaining))
+    }
+}
+
+impl ExactSizeIterator for ESetIter {}
+
+impl IntoIterator for ESet {
+    type Item = Result<ExpandTerm, Error>;
+    type IntoIter = ESetIter;
+
+    fn into_iter(self) -> ESetIter {
+        ESetIter::new(self)
+    }
+}
+
+impl IntoIterator for &ESet {
+    type Item = Result<ExpandTerm, Error>;
+    type IntoIter = ESetIter;
+
+    fn into_iter(self) -> ESetIter {
+        ESetIter::new(self.clone())
+    }
+}
+
+pub struct ESetIterator {
+    pub cxxp: UniquePtr<ffi::ESetIterator>,
+}
+
+impl ESetIterator {
+    /// Get the term at the current position, fails if it isn't valid UTF-8
+    pub fn get_term(&mut self) -> Result<String, Error> {
+        Ok(ffi::eset_iterator_get_term(self.cxxp.pin_mut())?)
+    }
+
+    /// Get the weight for the current position
+    pub fn get_weight(&mut self) -> Result<f64, Error> {
+        Ok(ffi::eset_iterator_get_weight(self.cxxp.pin_mut())?)
+    }
+
+    pub fn eq(&mut self, other: &mut ESetIterator) -> bool {
+        ffi::eset_iterator_eq(self.cxxp.pin_mut(), other.cxxp.pin_mut())
+    }
+
+    /// Move to the next term, fails if already at the end
+    #[allow(clippy::should_implement_trait)] // a cursor like Xapian's operator++, see ESet::iter for an Iterator
+    pub fn next(&mut self) -> Result<(), Error> {
+        ffi::eset_iterator_next(self.cxxp.pin_mut())?;
+        Ok(())
+    }
 }
 
 pub struct Database {
@@ -465,34 +1955,136 @@ pub struct Database {
 
 #[allow(unused_unsafe)]
 impl Database {
-    pub fn new() -> Result<Self, cxx::Exception> {
+    pub fn new() -> Result<Self, Error> {
         Ok(Self { cxxp: ffi::new_database()? })
     }
 
-    pub fn new_with_path(path: &str, db_type: i32) -> Result<Self, cxx::Exception> {
+    pub fn new_with_path(path: &str, db_type: i32) -> Result<Self, Error> {
         Ok(Self {
             cxxp: ffi::new_database_with_path(path, db_type)?,
         })
     }
 
-    pub fn new_enquire(&mut self) -> Result<Enquire, cxx::Exception> {
+    pub fn new_enquire(&mut self) -> Result<Enquire, Error> {
         let obj = ffi::new_enquire(self.cxxp.pin_mut())?;
 
-        Ok(Enquire { cxxp: obj, sorter: None })
+        Ok(Enquire {
+            cxxp: obj,
+            sorter: None,
+            matchspies: Vec::new(),
+            value_count_spies: Vec::new(),
+        })
     }
 
-    pub fn add_database(&mut self, database: &mut Database) -> Result<(), cxx::Exception> {
+    pub fn add_database(&mut self, database: &mut Database) -> Result<(), Error> {
         ffi::add_database(self.cxxp.pin_mut(), database.cxxp.pin_mut())?;
         Ok(())
     }
 
-    pub fn reopen(&mut self) -> Result<(), cxx::Exception> {
+    pub fn reopen(&mut self) -> Result<(), Error> {
         Ok(ffi::database_reopen(self.cxxp.pin_mut())?)
     }
 
-    pub fn close(&mut self) -> Result<(), cxx::Exception> {
+    pub fn close(&mut self) -> Result<(), Error> {
         Ok(ffi::database_close(self.cxxp.pin_mut())?)
     }
+
+    /// Get a document from the database, given its document id
+    pub fn get_document(&mut self, docid: u32) -> Result<Document, Error> {
+        Ok(Document {
+            cxxp: ffi::database_get_document(self.cxxp.pin_mut(), docid)?,
+        })
+    }
+
+    /// Get the number of documents in the database
+    pub fn get_doccount(&mut self) -> Result<usize, Error> {
+        Ok(ffi::database_get_doccount(self.cxxp.pin_mut())? as usize)
+    }
+
+    /// Get the highest document id which has been used in the database
+    pub fn get_lastdocid(&mut self) -> Result<u32, Error> {
+        Ok(ffi::database_get_lastdocid(self.cxxp.pin_mut())?)
+    }
+
+    /// Get the average length of the documents in the database
+    pub fn get_avlength(&mut self) -> Result<f64, Error> {
+        Ok(ffi::database_get_avlength(self.cxxp.pin_mut())?)
+    }
+
+    /// Get the number of documents in the database indexed by a given term
+    pub fn get_termfreq(&mut self, term: &str) -> Result<u32, Error> {
+        Ok(ffi::database_get_termfreq(self.cxxp.pin_mut(), term)?)
+    }
+
+    /// Return the total number of occurrences of the given term
+    pub fn get_collection_freq(&mut self, term: &str) -> Result<u32, Error> {
+        Ok(ffi::database_get_collection_freq(self.cxxp.pin_mut(), term)?)
+    }
+
+    /// Check if a given term exists in the database
+    pub fn term_exists(&mut self, term: &str) -> Result<bool, Error> {
+        Ok(ffi::database_term_exists(self.cxxp.pin_mut(), term)?)
+    }
+
+    /// Return the frequency of a given value slot, i.e. the number of documents with a value in it
+    pub fn get_value_freq(&mut self, slot: u32) -> Result<u32, Error> {
+        Ok(ffi::database_get_value_freq(self.cxxp.pin_mut(), slot)?)
+    }
+
+    /// Get a lower bound on the values stored in the given value slot
+    pub fn get_value_lower_bound(&mut self, slot: u32) -> Result<Vec<u8>, Error> {
+        Ok(ffi::database_get_value_lower_bound(self.cxxp.pin_mut(), slot)?)
+    }
+
+    /// Get an upper bound on the values stored in the given value slot
+    pub fn get_value_upper_bound(&mut self, slot: u32) -> Result<Vec<u8>, Error> {
+        Ok(ffi::database_get_value_upper_bound(self.cxxp.pin_mut(), slot)?)
+    }
+
+    /// Get the length of a document
+    pub fn get_doclength(&mut self, docid: u32) -> Result<u32, Error> {
+        Ok(ffi::database_get_doclength(self.cxxp.pin_mut(), docid)?)
+    }
+
+    /// Get a UUID for the database, empty if the backend doesn't support it
+    pub fn get_uuid(&mut self) -> Result<String, Error> {
+        Ok(ffi::database_get_uuid(self.cxxp.pin_mut())?)
+    }
+
+    /// Get the revision of the database
+    pub fn get_revision(&mut self) -> Result<u64, Error> {
+        Ok(ffi::database_get_revision(self.cxxp.pin_mut())?)
+    }
+
+    /// Get the user-specified metadata associated with a given key, empty if not set
+    pub fn get_metadata(&mut self, key: &str) -> Result<Vec<u8>, Error> {
+        Ok(ffi::database_get_metadata(self.cxxp.pin_mut(), key)?)
+    }
+
+    /// Iterate over the metadata keys starting with `prefix`, all keys if it is empty
+    pub fn metadata_keys(&mut self, prefix: &str) -> Result<TermIter, Error> {
+        Ok(TermIter::new(ffi::database_metadata_keys_begin(self.cxxp.pin_mut(), prefix)?, ffi::new_term_iterator_end()?))
+    }
+
+    /// Iterate over the words in the spelling dictionary
+    pub fn spellings(&mut self) -> Result<TermIter, Error> {
+        Ok(TermIter::new(ffi::database_spellings_begin(self.cxxp.pin_mut())?, ffi::new_term_iterator_end()?))
+    }
+
+    /// Suggest a spelling correction for `word` within `max_edit_distance` edits, empty if there is none
+    pub fn get_spelling_suggestion(&mut self, word: &str, max_edit_distance: u32) -> Result<String, Error> {
+        Ok(ffi::database_get_spelling_suggestion(self.cxxp.pin_mut(), word, max_edit_distance)?)
+    }
+
+    /// Iterate over the synonyms of `term`
+    pub fn synonyms(&mut self, term: &str) -> Result<TermIter, Error> {
+        Ok(TermIter::new(ffi::database_synonyms_begin(self.cxxp.pin_mut(), term)?, ffi::new_term_iterator_end()?))
+    }
+
+    /// Iterate over the terms starting with `prefix` which have synonyms, all of them if it is empty
+    pub fn synonym_keys(&mut self, prefix: &str) -> Result<TermIter, Error> {
+        Ok(TermIter::new(ffi::database_synonym_keys_begin(self.cxxp.pin_mut(), prefix)?, ffi::new_term_iterator_end()?))
+    }
 }
 
 pub struct WritableDatabase {
@@ -501,263 +2093,1644 @@ pub struct WritableDatabase {
 
 #[allow(unused_unsafe)]
 impl WritableDatabase {
-    pub fn new(path: &str, action: i32, db_type: i32) -> Result<Self, cxx::Exception> {
+    pub fn new(path: &str, action: i32, db_type: i32) -> Result<Self, Error> {
         match ffi::new_writable_database_with_path(path, action, db_type) {
             Ok(cxxp) => Ok(WritableDatabase { cxxp }),
-            Err(e) => Err(e),
+            Err(e) => Err(e.into()),
         }
     }
 
-    pub fn delete_document(&mut self, unique_term: &str) -> Result<(), cxx::Exception> {
+    pub fn delete_document(&mut self, unique_term: &str) -> Result<(), Error> {
         ffi::delete_document(self.cxxp.pin_mut(), unique_term)?;
         Ok(())
     }
 
-    pub fn replace_document(&mut self, unique_term: &str, doc: &mut Document) -> Result<u32, cxx::Exception> {
+    pub fn replace_document(&mut self, unique_term: &str, doc: &mut Document) -> Result<u32, Error> {
         let docid = ffi::replace_document(self.cxxp.pin_mut(), unique_term, doc.cxxp.pin_mut())?;
         Ok(docid)
     }
 
-    pub fn commit(&mut self) -> Result<(), cxx::Exception> {
-        ffi::commit(self.cxxp.pin_mut())
+    pub fn commit(&mut self) -> Result<(), Error> {
+        ffi::commit(self.cxxp.pin_mut())?;
+        Ok(())
     }
 
-    pub fn close(&mut self) -> Result<(), cxx::Exception> {
+    pub fn close(&mut self) -> Result<(), Error> {
         ffi::close(self.cxxp.pin_mut())?;
         Ok(())
     }
 
-    pub fn get_doccount(&mut self) -> Result<usize, cxx::Exception> {
+    pub fn get_doccount(&mut self) -> Result<usize, Error> {
         let res = ffi::get_doccount(self.cxxp.pin_mut())?;
         Ok(res)
     }
-}
-
-pub struct Document {
-    cxxp: UniquePtr<ffi::Document>,
-}
 
-#[allow(unused_unsafe)]
-impl Document {
-    pub fn new() -> Result<Self, cxx::Exception> {
-        Ok(Self { cxxp: ffi::new_document()? })
+    /// Add a new document to the database, returns the docid allocated to it
+    pub fn add_document(&mut self, doc: &mut Document) -> Result<u32, Error> {
+        Ok(ffi::writable_database_add_document(self.cxxp.pin_mut(), doc.cxxp.pin_mut())?)
     }
 
-    pub fn add_string(&mut self, slot: u32, data: &str) -> Result<(), cxx::Exception> {
-        ffi::add_string(self.cxxp.pin_mut(), slot, data)?;
+    /// Replace the document with id `docid`, or add it with that id if it doesn't exist
+    pub fn replace_document_by_docid(&mut self, docid: u32, doc: &mut Document) -> Result<(), Error> {
+        ffi::writable_database_replace_document_by_docid(self.cxxp.pin_mut(), docid, doc.cxxp.pin_mut())?;
         Ok(())
     }
 
-    pub fn add_int(&mut self, slot: u32, data: i32) -> Result<(), cxx::Exception> {
-        ffi::add_int(self.cxxp.pin_mut(), slot, data)?;
+    /// Delete the document with id `docid`, fails with [`Error::DocNotFound`] if it doesn't exist
+    pub fn delete_document_by_docid(&mut self, docid: u32) -> Result<(), Error> {
+        ffi::writable_database_delete_document_by_docid(self.cxxp.pin_mut(), docid)?;
         Ok(())
     }
 
-    pub fn add_long(&mut self, slot: u32, data: i64) -> Result<(), cxx::Exception> {
-        ffi::add_long(self.cxxp.pin_mut(), slot, data)?;
+    /// Begin a transaction, the changes made until [`WritableDatabase::commit_transaction`] are applied atomically
+    ///
+    /// If `flushed` is true, pending changes are committed before the transaction begins and the
+    /// transaction is committed to disk when it ends. Prefer [`WritableDatabase::transaction`], which
+    /// can't be left open by mistake.
+    pub fn begin_transaction(&mut self, flushed: bool) -> Result<(), Error> {
+        ffi::writable_database_begin_transaction(self.cxxp.pin_mut(), flushed)?;
         Ok(())
     }
 
-    pub fn add_double(&mut self, slot: u32, data: f64) -> Result<(), cxx::Exception> {
-        ffi::add_double(self.cxxp.pin_mut(), slot, data)?;
+    /// Complete the current transaction
+    pub fn commit_transaction(&mut self) -> Result<(), Error> {
+        ffi::writable_database_commit_transaction(self.cxxp.pin_mut())?;
         Ok(())
     }
 
-    pub fn set_data(&mut self, data: &str) -> Result<(), cxx::Exception> {
-        ffi::set_data(self.cxxp.pin_mut(), data)?;
+    /// Abort the current transaction, discarding the changes made since it began
+    pub fn cancel_transaction(&mut self) -> Result<(), Error> {
+        ffi::writable_database_cancel_transaction(self.cxxp.pin_mut())?;
         Ok(())
     }
 
-    pub fn get_data(&mut self) -> Result<String, cxx::Exception> {
-        let res = ffi::get_doc_data(self.cxxp.pin_mut())?;
-        Ok(res.to_string())
-    }
-
-    pub fn add_boolean_term(&mut self, data: &str) -> Result<(), cxx::Exception> {
-        ffi::add_boolean_term(self.cxxp.pin_mut(), data)?;
+    /// Set the user-specified metadata associated with `key`, an empty `metadata` removes the key
+    pub fn set_metadata(&mut self, key: &str, metadata: &[u8]) -> Result<(), Error> {
+        ffi::writable_database_set_metadata(self.cxxp.pin_mut(), key, metadata)?;
         Ok(())
     }
-}
-
-pub struct Stem {
-    cxxp: UniquePtr<ffi::Stem>,
-}
 
-#[allow(unused_unsafe)]
-impl Stem {
-    pub fn new(lang: &str) -> Result<Self, cxx::Exception> {
-        let obj = ffi::new_stem(lang)?;
-        Ok(Self { cxxp: obj })
+    /// Get the user-specified metadata associated with `key`, empty if not set
+    pub fn get_metadata(&mut self, key: &str) -> Result<Vec<u8>, Error> {
+        Ok(ffi::writable_database_get_metadata(self.cxxp.pin_mut(), key)?)
     }
-}
-
-pub struct TermGenerator {
-    cxxp: UniquePtr<ffi::TermGenerator>,
-}
 
-impl TermGenerator {
-    pub fn new() -> Result<Self, cxx::Exception> {
-        Ok(Self {
-            cxxp: ffi::new_termgenerator()?,
-        })
+    /// Iterate over the metadata keys starting with `prefix`, all keys if it is empty
+    pub fn metadata_keys(&mut self, prefix: &str) -> Result<TermIter, Error> {
+        Ok(TermIter::new(ffi::writable_database_metadata_keys_begin(self.cxxp.pin_mut(), prefix)?, ffi::new_term_iterator_end()?))
     }
-}
 
-#[allow(unused_unsafe)]
-impl TermGenerator {
-    pub fn set_stemmer(&mut self, mut stem: Stem) -> Result<(), cxx::Exception> {
-        ffi::set_stemmer(self.cxxp.pin_mut(), stem.cxxp.pin_mut())?;
+    /// Add a word to the spelling dictionary, or increase its frequency by `freqinc` if it is already there
+    pub fn add_spelling(&mut self, word: &str, freqinc: u32) -> Result<(), Error> {
+        ffi::writable_database_add_spelling(self.cxxp.pin_mut(), word, freqinc)?;
         Ok(())
     }
 
-    pub fn set_flags(&mut self, toggle: i32, mask: i32) -> Result<(), cxx::Exception> {
-        ffi::set_flags(self.cxxp.pin_mut(), toggle as i32, mask as i32)?;
+    /// Decrease the frequency of a word in the spelling dictionary by `freqdec`, removing it when it reaches zero
+    pub fn remove_spelling(&mut self, word: &str, freqdec: u32) -> Result<(), Error> {
+        ffi::writable_database_remove_spelling(self.cxxp.pin_mut(), word, freqdec)?;
         Ok(())
     }
 
-    pub fn set_document(&mut self, doc: &mut Document) -> Result<(), cxx::Exception> {
-        ffi::set_document(self.cxxp.pin_mut(), doc.cxxp.pin_mut());
+    /// Add `synonym` as a synonym of `term`
+    pub fn add_synonym(&mut self, term: &str, synonym: &str) -> Result<(), Error> {
+        ffi::writable_database_add_synonym(self.cxxp.pin_mut(), term, synonym)?;
         Ok(())
     }
 
-    pub fn index_text_with_prefix(&mut self, data: &str, prefix: &str) -> Result<(), cxx::Exception> {
-        ffi::index_text_with_prefix(self.cxxp.pin_mut(), data, prefix);
+    /// Remove `synonym` from the synonyms of `term`
+    pub fn remove_synonym(&mut self, term: &str, synonym: &str) -> Result<(), Error> {
+        ffi::writable_database_remove_synonym(self.cxxp.pin_mut(), term, synonym)?;
         Ok(())
     }
 
-    pub fn index_text(&mut self, data: &str) -> Result<(), cxx::Exception> {
-        Ok(ffi::index_text(self.cxxp.pin_mut(), data)?)
+    /// Remove all synonyms of `term`
+    pub fn clear_synonyms(&mut self, term: &str) -> Result<(), Error> {
+        ffi::writable_database_clear_synonyms(self.cxxp.pin_mut(), term)?;
+        Ok(())
     }
 
-    pub fn index_int(&mut self, data: i32, prefix: &str) -> Result<(), cxx::Exception> {
-        Ok(ffi::index_int(self.cxxp.pin_mut(), data, prefix)?)
+    /// Iterate over the words in the spelling dictionary, including uncommitted changes
+    pub fn spellings(&mut self) -> Result<TermIter, Error> {
+        Ok(TermIter::new(ffi::writable_database_spellings_begin(self.cxxp.pin_mut())?, ffi::new_term_iterator_end()?))
     }
 
-    pub fn index_long(&mut self, data: i64, prefix: &str) -> Result<(), cxx::Exception> {
-        Ok(ffi::index_long(self.cxxp.pin_mut(), data, prefix)?)
+    /// Iterate over the synonyms of `term`, including uncommitted changes
+    pub fn synonyms(&mut self, term: &str) -> Result<TermIter, Error> {
+        Ok(TermIter::new(ffi::writable_database_synonyms_begin(self.cxxp.pin_mut(), term)?, ffi::new_term_iterator_end()?))
     }
 
-    pub fn index_float(&mut self, data: f32, prefix: &str) -> Result<(), cxx::Exception> {
-        Ok(ffi::index_float(self.cxxp.pin_mut(), data, prefix)?)
+    /// Iterate over the terms starting with `prefix` which have synonyms, all of them if it is empty
+    pub fn synonym_keys(&mut self, prefix: &str) -> Result<TermIter, Error> {
+        Ok(TermIter::new(ffi::writable_database_synonym_keys_begin(self.cxxp.pin_mut(), prefix)?, ffi::new_term_iterator_end()?))
     }
 
-    pub fn index_double(&mut self, data: f64, prefix: &str) -> Result<(), cxx::Exception> {
-        Ok(ffi::index_double(self.cxxp.pin_mut(), data, prefix)?)
+    /// Begin a transaction which is cancelled when the returned guard is dropped without calling [`Transaction::commit`]
+    pub fn transaction(&mut self, flushed: bool) -> Result<Transaction<'_>, Error> {
+        self.begin_transaction(flushed)?;
+        Ok(Transaction { db: self, finished: false })
     }
 }
 
-#[warn(unused_unsafe)]
-pub struct ValueCountMatchSpy {
-    pub cxxp: UniquePtr<ffi::ValueCountMatchSpy>,
+/// A transaction on a [`WritableDatabase`], see [`WritableDatabase::transaction`]
+///
+/// Documents are added through the guard, which borrows the database so it can't be committed
+/// or given a nested transaction behind the guard's back. Dropping it without calling
+/// [`Transaction::commit`] cancels the transaction, so a failed batch leaves the database as it
+/// was before.
+pub struct Transaction<'a> {
+    db: &'a mut WritableDatabase,
+    finished: bool,
 }
 
-impl ValueCountMatchSpy {
-    pub fn new(slot: u32) -> Result<Self, cxx::Exception> {
-        Ok(Self {
-            cxxp: ffi::new_value_count_match_spy(slot)?,
-        })
+impl Transaction<'_> {
+    /// Commit the changes made in this transaction
+    pub fn commit(mut self) -> Result<(), Error> {
+        self.finished = true;
+        self.db.commit_transaction()
     }
 
-    // https://xapian.org/docs/facets#toc-entry-5
-    // return Xapian::TermIterator
-    pub fn values_begin(&mut self) -> Result<TermIterator, cxx::Exception> {
-        Ok(TermIterator {
-            cxxp: ffi::value_count_matchspy_values_begin(self.cxxp.pin_mut())?,
-        })
+    /// Discard the changes made in this transaction
+    pub fn cancel(mut self) -> Result<(), Error> {
+        self.finished = true;
+        self.db.cancel_transaction()
     }
 
-    pub fn values_end(&mut self) -> Result<TermIterator, cxx::Exception> {
-        Ok(TermIterator {
-            cxxp: ffi::value_count_matchspy_values_end(self.cxxp.pin_mut())?,
-        })
+    pub fn get_doccount(&mut self) -> Result<usize, Error> {
+        self.db.get_doccount()
     }
 
-    pub fn get_total(&mut self) -> i32 {
-        ffi::value_count_matchspy_get_total(self.cxxp.pin_mut())
+    /// See [`WritableDatabase::add_document`]
+    pub fn add_document(&mut self, doc: &mut Document) -> Result<u32, Error> {
+        self.db.add_document(doc)
     }
-}
 
-pub struct RangeProcessor {
-    pub cxxp: UniquePtr<ffi::RangeProcessor>,
-}
+    /// See [`WritableDatabase::replace_document`]
+    pub fn replace_document(&mut self, unique_term: &str, doc: &mut Document) -> Result<u32, Error> {
+        self.db.replace_document(unique_term, doc)
+    }
 
-impl RangeProcessor {
-    pub fn new(slot: u32, prefix: &str, flags: crate::constants::RangeProcessorFlags) -> Result<Self, cxx::Exception> {
-        Ok(Self {
-            cxxp: ffi::new_range_processor(slot, prefix, flags as i32)?,
-        })
+    /// See [`WritableDatabase::replace_document_by_docid`]
+    pub fn replace_document_by_docid(&mut self, docid: u32, doc: &mut Document) -> Result<(), Error> {
+        self.db.replace_document_by_docid(docid, doc)
+    }
+
+    /// See [`WritableDatabase::delete_document`]
+    pub fn delete_document(&mut self, unique_term: &str) -> Result<(), Error> {
+        self.db.delete_document(unique_term)
+    }
+
+    /// See [`WritableDatabase::delete_document_by_docid`]
+    pub fn delete_document_by_docid(&mut self, docid: u32) -> Result<(), Error> {
+        self.db.delete_document_by_docid(docid)
+    }
+}
+
+impl Drop for Transaction<'_> {
+    fn drop(&mut self) {
+        if !self.finished {
+            // errors can't be reported from drop, and the transaction is discarded either way
+            let _ = self.db.cancel_transaction();
+        }
+    }
+}
+
+pub struct Document {
+    cxxp: UniquePtr<ffi::Document>,
+}
+
+#[allow(unused_unsafe)]
+impl Document {
+    pub fn new() -> Result<Self, Error> {
+        Ok(Self { cxxp: ffi::new_document()? })
+    }
+
+    pub fn add_string(&mut self, slot: u32, data: &str) -> Result<(), Error> {
+        ffi::add_string(self.cxxp.pin_mut(), slot, data)?;
+        Ok(())
+    }
+
+    pub fn add_int(&mut self, slot: u32, data: i32) -> Result<(), Error> {
+        ffi::add_int(self.cxxp.pin_mut(), slot, data)?;
+        Ok(())
+    }
+
+    pub fn add_long(&mut self, slot: u32, data: i64) -> Result<(), Error> {
+        ffi::add_long(self.cxxp.pin_mut(), slot, data)?;
+        Ok(())
+    }
+
+    pub fn add_double(&mut self, slot: u32, data: f64) -> Result<(), Error> {
+        ffi::add_double(self.cxxp.pin_mut(), slot, data)?;
+        Ok(())
+    }
+
+    pub fn set_data(&mut self, data: &str) -> Result<(), Error> {
+        ffi::set_data(self.cxxp.pin_mut(), data)?;
+        Ok(())
+    }
+
+    /// Get the document data, fails if it isn't valid UTF-8, see [`Document::get_data_bytes`]
+    pub fn get_data(&mut self) -> Result<String, Error> {
+        let res = ffi::get_doc_data(self.cxxp.pin_mut())?;
+        Ok(res.to_string())
+    }
+
+    /// Set the document data to arbitrary bytes, e.g. a bincode or msgpack encoded payload
+    pub fn set_data_bytes(&mut self, data: &[u8]) -> Result<(), Error> {
+        ffi::document_set_data_bytes(self.cxxp.pin_mut(), data)?;
+        Ok(())
+    }
+
+    /// Get the document data as bytes
+    pub fn get_data_bytes(&mut self) -> Result<Vec<u8>, Error> {
+        Ok(ffi::document_get_data_bytes(self.cxxp.pin_mut())?)
+    }
+
+    /// Add a binary value to `slot`, replacing any existing value
+    pub fn add_value_bytes(&mut self, slot: u32, value: &[u8]) -> Result<(), Error> {
+        ffi::document_add_value_bytes(self.cxxp.pin_mut(), slot, value)?;
+        Ok(())
+    }
+
+    /// Get the value stored in `slot` as bytes, empty if there is none
+    pub fn get_value_bytes(&mut self, slot: u32) -> Result<Vec<u8>, Error> {
+        Ok(ffi::document_get_value_bytes(self.cxxp.pin_mut(), slot)?)
+    }
+
+    pub fn add_boolean_term(&mut self, data: &str) -> Result<(), Error> {
+        ffi::add_boolean_term(self.cxxp.pin_mut(), data)?;
+        Ok(())
+    }
+
+    /// Get the document id, 0 if this document wasn't read from a database
+    pub fn get_docid(&mut self) -> Result<u32, Error> {
+        Ok(ffi::document_get_docid(self.cxxp.pin_mut())?)
+    }
+
+    /// Add a term to the document, increasing its wdf by `wdf_inc`
+    pub fn add_term(&mut self, term: &str, wdf_inc: u32) -> Result<(), Error> {
+        ffi::document_add_term(self.cxxp.pin_mut(), term, wdf_inc)?;
+        Ok(())
+    }
+
+    /// Add an occurrence of `term` at position `pos`, increasing its wdf by `wdf_inc`
+    pub fn add_posting(&mut self, term: &str, pos: u32, wdf_inc: u32) -> Result<(), Error> {
+        ffi::document_add_posting(self.cxxp.pin_mut(), term, pos, wdf_inc)?;
+        Ok(())
+    }
+
+    /// Remove a term and all its postings from the document, fails with [`Error::InvalidArgument`] if it isn't there
+    pub fn remove_term(&mut self, term: &str) -> Result<(), Error> {
+        ffi::document_remove_term(self.cxxp.pin_mut(), term)?;
+        Ok(())
+    }
+
+    /// Remove the occurrence of `term` at position `pos`, decreasing its wdf by `wdf_dec`
+    pub fn remove_posting(&mut self, term: &str, pos: u32, wdf_dec: u32) -> Result<(), Error> {
+        ffi::document_remove_posting(self.cxxp.pin_mut(), term, pos, wdf_dec)?;
+        Ok(())
+    }
+
+    /// Remove all terms and postings from the document
+    pub fn clear_terms(&mut self) -> Result<(), Error> {
+        ffi::document_clear_terms(self.cxxp.pin_mut())?;
+        Ok(())
+    }
+
+    /// Count the terms in the document
+    pub fn termlist_count(&mut self) -> Result<u32, Error> {
+        Ok(ffi::document_termlist_count(self.cxxp.pin_mut())?)
+    }
+
+    /// Iterate over the terms in the document, in ascending term order
+    pub fn termlist(&mut self) -> Result<TermListIter, Error> {
+        Ok(TermListIter {
+            begin: TermIterator {
+                cxxp: ffi::document_termlist_begin(self.cxxp.pin_mut())?,
+            },
+            end: TermIterator {
+                cxxp: ffi::document_termlist_end(self.cxxp.pin_mut())?,
+            },
+            done: false,
+        })
+    }
+
+    /// Get the value stored in `slot`, empty if there is none, fails if it isn't valid UTF-8, see [`Document::get_value_bytes`]
+    pub fn get_value(&mut self, slot: u32) -> Result<String, Error> {
+        Ok(ffi::document_get_value(self.cxxp.pin_mut(), slot)?)
+    }
+
+    /// Remove the value stored in `slot`
+    pub fn remove_value(&mut self, slot: u32) -> Result<(), Error> {
+        ffi::document_remove_value(self.cxxp.pin_mut(), slot)?;
+        Ok(())
+    }
+
+    /// Remove all values from the document
+    pub fn clear_values(&mut self) -> Result<(), Error> {
+        ffi::document_clear_values(self.cxxp.pin_mut())?;
+        Ok(())
+    }
+
+    /// Count the value slots used in the document
+    pub fn values_count(&mut self) -> Result<u32, Error> {
+        Ok(ffi::document_values_count(self.cxxp.pin_mut())?)
+    }
+
+    /// Iterate over the values in the document, in ascending slot order
+    pub fn values(&mut self) -> Result<ValueIter, Error> {
+        Ok(ValueIter {
+            begin: ValueIterator {
+                cxxp: ffi::document_values_begin(self.cxxp.pin_mut())?,
+            },
+            end: ValueIterator {
+                cxxp: ffi::document_values_end(self.cxxp.pin_mut())?,
+            },
+            done: false,
+        })
+    }
+}
+
+/// A term of a [`Document`], as yielded by [`Document::termlist`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TermListItem {
+    pub term: String,
+    /// The within-document frequency of the term
+    pub wdf: u32,
+}
+
+/// Iterator over the terms of a [`Document`]
+pub struct TermListIter {
+    begin: TermIterator,
+    end: TermIterator,
+    // set once advancing fails, the position is unknown after that
+    done: bool,
+}
+
+impl Iterator for TermListIter {
+    type Item = Result<TermListItem, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done || self.begin.eq(&mut self.end) {
+            return None;
+        }
+        let item = self.begin.get_term().and_then(|term| Ok(TermListItem { term, wdf: self.begin.get_wdf()? }));
+        if let Err(e) = self.begin.next() {
+            self.done = true;
+            return Some(Err(e));
+        }
+        Some(item)
+    }
+}
+
+/// A value of a [`Document`], as yielded by [`Document::values`]
+///
+/// Values are binary, e.g. the ones written by [`Document::add_int`] are sortable serialised.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ValueItem {
+    pub slot: u32,
+    pub value: Vec<u8>,
+}
+
+/// Iterator over the values of a [`Document`]
+pub struct ValueIter {
+    begin: ValueIterator,
+    end: ValueIterator,
+    // set once advancing fails, the position is unknown after that
+    done: bool,
+}
+
+impl Iterator for ValueIter {
+    type Item = Result<ValueItem, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done || self.begin.eq(&mut self.end) {
+            return None;
+        }
+        let item = self.begin.get_valueno().and_then(|slot| Ok(ValueItem { slot, value: self.begin.get_value()? }));
+        if let Err(e) = self.begin.next() {
+            self.done = true;
+            return Some(Err(e));
+        }
+        Some(item)
+    }
+}
+
+pub struct ValueIterator {
+    pub cxxp: UniquePtr<ffi::ValueIterator>,
+}
+
+impl ValueIterator {
+    /// Get the value at the current position
+    pub fn get_value(&mut self) -> Result<Vec<u8>, Error> {
+        Ok(ffi::value_iterator_get_value(self.cxxp.pin_mut())?)
+    }
+
+    /// Get the value slot number of the current position
+    pub fn get_valueno(&mut self) -> Result<u32, Error> {
+        Ok(ffi::value_iterator_get_valueno(self.cxxp.pin_mut())?)
+    }
+
+    pub fn eq(&mut self, other: &mut ValueIterator) -> bool {
+        ffi::value_iterator_eq(self.cxxp.pin_mut(), other.cxxp.pin_mut())
+    }
+
+    /// Move to the next value, the accessors and this return a `RangeError` at the end
+    #[allow(clippy::should_implement_trait)] // a cursor like Xapian's operator++, see ValueIter for an Iterator
+    pub fn next(&mut self) -> Result<(), Error> {
+        ffi::value_iterator_next(self.cxxp.pin_mut())?;
+        Ok(())
+    }
+}
+
+pub struct Stem {
+    cxxp: UniquePtr<ffi::Stem>,
+}
+
+#[allow(unused_unsafe)]
+impl Stem {
+    pub fn new(lang: &str) -> Result<Self, Error> {
+        let obj = ffi::new_stem(lang)?;
+        Ok(Self { cxxp: obj })
+    }
+}
+
+pub struct TermGenerator {
+    cxxp: UniquePtr<ffi::TermGenerator>,
+}
+
+impl TermGenerator {
+    pub fn new() -> Result<Self, Error> {
+        Ok(Self {
+            cxxp: ffi::new_termgenerator()?,
+        })
+    }
+}
+
+#[allow(unused_unsafe)]
+impl TermGenerator {
+    pub fn set_stemmer(&mut self, mut stem: Stem) -> Result<(), Error> {
+        ffi::set_stemmer(self.cxxp.pin_mut(), stem.cxxp.pin_mut())?;
+        Ok(())
+    }
+
+    pub fn set_flags(&mut self, toggle: i32, mask: i32) -> Result<(), Error> {
+        ffi::set_flags(self.cxxp.pin_mut(), toggle as i32, mask as i32)?;
+        Ok(())
+    }
+
+    pub fn set_document(&mut self, doc: &mut Document) -> Result<(), Error> {
+        ffi::set_document(self.cxxp.pin_mut(), doc.cxxp.pin_mut())?;
+        Ok(())
+    }
+
+    pub fn index_text_with_prefix(&mut self, data: &str, prefix: &str) -> Result<(), Error> {
+        ffi::index_text_with_prefix(self.cxxp.pin_mut(), data, prefix)?;
+        Ok(())
+    }
+
+    pub fn index_text(&mut self, data: &str) -> Result<(), Error> {
+        Ok(ffi::index_text(self.cxxp.pin_mut(), data)?)
+    }
+
+    pub fn index_int(&mut self, data: i32, prefix: &str) -> Result<(), Error> {
+        Ok(ffi::index_int(self.cxxp.pin_mut(), data, prefix)?)
+    }
+
+    pub fn index_long(&mut self, data: i64, prefix: &str) -> Result<(), Error> {
+        Ok(ffi::index_long(self.cxxp.pin_mut(), data, prefix)?)
+    }
+
+    pub fn index_float(&mut self, data: f32, prefix: &str) -> Result<(), Error> {
+        Ok(ffi::index_float(self.cxxp.pin_mut(), data, prefix)?)
+    }
+
+    pub fn index_double(&mut self, data: f64, prefix: &str) -> Result<(), Error> {
+        Ok(ffi::index_double(self.cxxp.pin_mut(), data, prefix)?)
+    }
+
+    /// Set the stopper used to skip stop words when indexing, the term generator takes ownership of it
+    pub fn set_stopper(&mut self, stopper: impl Into<Stopper>) -> Result<(), Error> {
+        ffi::term_generator_set_stopper(self.cxxp.pin_mut(), stopper.into().cxxp)?;
+        Ok(())
+    }
+
+    /// Set how the stopper is used, it has no effect without [`TermGenerator::set_stopper`]
+    pub fn set_stopper_strategy(&mut self, strategy: constants::StopStrategy) -> Result<(), Error> {
+        ffi::term_generator_set_stopper_strategy(self.cxxp.pin_mut(), strategy as i32)?;
+        Ok(())
+    }
+}
+
+/// Decides which words are stop words, see [`TermGenerator::set_stopper`] and [`QueryParser::set_stopper`]
+///
+/// Built from a [`SimpleStopper`], a [`StemStopper`] or a closure with [`Stopper::from_fn`].
+pub struct Stopper {
+    cxxp: UniquePtr<ffi::Stopper>,
+}
+
+impl Stopper {
+    /// `is_stop` is called for each word and returns whether it is a stop word
+    pub fn from_fn(is_stop: impl Fn(&str) -> bool + 'static) -> Result<Self, Error> {
+        let callback = Box::new(StopperCallback { is_stop: Box::new(is_stop) });
+        Ok(Self {
+            cxxp: ffi::new_rust_stopper(callback)?,
+        })
+    }
+
+    pub fn is_stop(&self, word: &str) -> Result<bool, Error> {
+        Ok(ffi::stopper_is_stop(&self.cxxp, word)?)
+    }
+}
+
+struct StopperCallback {
+    is_stop: Box<dyn Fn(&str) -> bool>,
+}
+
+fn stopper_is_stop_word(callback: &StopperCallback, word: &str) -> Result<bool, String> {
+    catch_callback_panic("Stopper", || (callback.is_stop)(word))
+}
+
+/// A stopper with a fixed list of stop words
+pub struct SimpleStopper {
+    cxxp: UniquePtr<ffi::Stopper>,
+}
+
+impl SimpleStopper {
+    pub fn new() -> Result<Self, Error> {
+        Ok(Self {
+            cxxp: ffi::new_simple_stopper()?,
+        })
+    }
+
+    pub fn from_words<S: AsRef<str>>(words: impl IntoIterator<Item = S>) -> Result<Self, Error> {
+        let mut stopper = Self::new()?;
+        for word in words {
+            stopper.add(word.as_ref())?;
+        }
+        Ok(stopper)
+    }
+
+    /// Read the stop words from a file, separated by whitespace
+    pub fn from_file(path: impl AsRef<std::path::Path>) -> Result<Self, Error> {
+        let path = path.as_ref();
+        let words = std::fs::read_to_string(path).map_err(|e| Error::Io {
+            kind: e.kind(),
+            msg: format!("{}: {}", path.display(), e),
+        })?;
+        Self::from_words(words.split_whitespace())
+    }
+
+    pub fn add(&mut self, word: &str) -> Result<(), Error> {
+        ffi::simple_stopper_add(self.cxxp.pin_mut(), word)?;
+        Ok(())
+    }
+
+    pub fn is_stop(&self, word: &str) -> Result<bool, Error> {
+        Ok(ffi::stopper_is_stop(&self.cxxp, word)?)
+    }
+}
+
+impl From<SimpleStopper> for Stopper {
+    fn from(stopper: SimpleStopper) -> Self {
+        Self { cxxp: stopper.cxxp }
+    }
+}
+
+/// A stopper which also stops the stemmed forms of its stop words, requires xapian 1.5
+pub struct StemStopper {
+    cxxp: UniquePtr<ffi::Stopper>,
+}
+
+impl StemStopper {
+    pub fn new(stemmer: &Stem, strategy: constants::StemStrategy) -> Result<Self, Error> {
+        Ok(Self {
+            cxxp: ffi::new_stem_stopper(&stemmer.cxxp, strategy as i32)?,
+        })
+    }
+
+    /// Add a stop word and its stemmed form
+    pub fn add(&mut self, word: &str) -> Result<(), Error> {
+        ffi::stem_stopper_add(self.cxxp.pin_mut(), word)?;
+        Ok(())
+    }
+
+    pub fn is_stop(&self, word: &str) -> Result<bool, Error> {
+        Ok(ffi::stopper_is_stop(&self.cxxp, word)?)
+    }
+}
+
+impl From<StemStopper> for Stopper {
+    fn from(stopper: StemStopper) -> Self {
+        Self { cxxp: stopper.cxxp }
+    }
+}
+
+#[warn(unused_unsafe)]
+pub struct ValueCountMatchSpy {
+    cxxp: UniquePtr<ffi::ValueCountMatchSpy>,
+}
+
+impl ValueCountMatchSpy {
+    pub fn new(slot: u32) -> Result<Self, Error> {
+        Ok(Self {
+            cxxp: ffi::new_value_count_match_spy(slot)?,
+        })
+    }
+
+    // https://xapian.org/docs/facets#toc-entry-5
+    // return Xapian::TermIterator
+    pub fn values_begin(&mut self) -> Result<TermIterator, Error> {
+        Ok(TermIterator {
+            cxxp: ffi::value_count_matchspy_values_begin(self.cxxp.pin_mut())?,
+        })
+    }
+
+    pub fn values_end(&mut self) -> Result<TermIterator, Error> {
+        Ok(TermIterator {
+            cxxp: ffi::value_count_matchspy_values_end(self.cxxp.pin_mut())?,
+        })
+    }
+
+    pub fn get_total(&mut self) -> i32 {
+        ffi::value_count_matchspy_get_total(self.cxxp.pin_mut())
+    }
+
+    fn as_ptr(&self) -> *const ffi::ValueCountMatchSpy {
+        self.cxxp.as_ref().map_or(std::ptr::null(), |spy| spy as *const _)
+    }
+}
+
+pub struct NumberRangeProcessor {
+    pub cxxp: UniquePtr<ffi::RangeProcessor>,
+}
+
+impl NumberRangeProcessor {
+    pub fn new(slot: u32, prefix: &str, flags: crate::constants::RangeProcessorFlags) -> Result<Self, Error> {
+        Ok(Self {
+            cxxp: ffi::new_number_range_processor(slot, prefix, flags.bits())?,
+        })
+    }
+}
+
+/// Parses dates such as `2020-01-01` or `1/1/2020` into the `YYYYMMDD` form
+/// stored in the value slot.
+pub struct DateRangeProcessor {
+    pub cxxp: UniquePtr<ffi::RangeProcessor>,
+}
+
+impl DateRangeProcessor {
+    /// Two digit years are taken to fall in the hundred years starting at `epoch_year`
+    /// (xapian defaults this to 1970).
+    pub fn new(slot: u32, prefix: &str, flags: crate::constants::RangeProcessorFlags, epoch_year: i32) -> Result<Self, Error> {
+        Ok(Self {
+            cxxp: ffi::new_date_range_processor(slot, prefix, flags.bits(), epoch_year)?,
+        })
+    }
+}
+
+/// Parses byte sizes such as `10MB..2GB`, the value slot must hold the size in bytes
+/// encoded with `sortable_serialise`.
+///
+/// Requires xapian 1.5, with 1.4 `new` returns a `FeatureUnavailableError`.
+pub struct UnitRangeProcessor {
+    pub cxxp: UniquePtr<ffi::RangeProcessor>,
+}
+
+impl UnitRangeProcessor {
+    pub fn new(slot: u32, prefix: &str) -> Result<Self, Error> {
+        Ok(Self {
+            cxxp: ffi::new_unit_range_processor(slot, prefix)?,
+        })
+    }
+}
+
+// the built-in processors are released to the query parser, so it owns them from now on
+macro_rules! impl_builtin_range_processor {
+    ($($t:ident),*) => {
+        $(
+            impl IntoRangeProcessor for $t {
+                fn add_to(self, qp: &mut QueryParser, grouping: Option<&str>) -> Result<(), Error> {
+                    ffi::query_parser_add_rangeprocessor(qp.cxxp.pin_mut(), self.cxxp, grouping.is_some(), grouping.unwrap_or(""))?;
+                    Ok(())
+                }
+            }
+        )*
+    };
+}
+
+impl_builtin_range_processor!(NumberRangeProcessor, DateRangeProcessor, UnitRangeProcessor);
+
+#[warn(unused_unsafe)]
+pub struct TermIterator {
+    pub cxxp: UniquePtr<ffi::TermIterator>,
+}
+
+// std::string &term_iterator_get_termfreq_value(TermIterator &titer, int8_t &err);
+// int term_iterator_get_termfreq_freq(TermIterator &titer, int8_t &err);
+// bool term_iterator_eq(TermIterator &titer, TermIterator &other, int8_t &err);
+impl TermIterator {
+    /// The accessors and [`TermIterator::next`] return a `RangeError` at the end
+    pub fn get_termfreq_value(&mut self) -> Result<String, Error> {
+        Ok(ffi::term_iterator_get_termfreq_value(self.cxxp.pin_mut())?)
+    }
+
+    pub fn get_termfreq_freq(&mut self) -> Result<i32, Error> {
+        Ok(ffi::term_iterator_get_termfreq_freq(self.cxxp.pin_mut())?)
+    }
+
+    pub fn eq(&mut self, other: &mut TermIterator) -> bool {
+        let rs = ffi::term_iterator_eq(self.cxxp.pin_mut(), other.cxxp.pin_mut());
+        return rs;
+    }
+
+    #[allow(clippy::should_implement_trait)] // a cursor like Xapian's operator++, see TermIter for an Iterator
+    pub fn next(&mut self) -> Result<(), Error> {
+        ffi::term_iterator_next(self.cxxp.pin_mut())?;
+        Ok(())
+    }
+
+    /// Get the term at the current position, fails if it isn't valid UTF-8
+    pub fn get_term(&mut self) -> Result<String, Error> {
+        Ok(ffi::term_iterator_get_term(self.cxxp.pin_mut())?)
+    }
+
+    /// Get the within-document frequency of the current term
+    pub fn get_wdf(&mut self) -> Result<u32, Error> {
+        Ok(ffi::term_iterator_get_wdf(self.cxxp.pin_mut())?)
+    }
+}
+
+/// Registry of user subclasses of Xapian classes, needed to unserialise queries using them
+pub struct Registry {
+    pub cxxp: UniquePtr<ffi::Registry>,
+}
+
+impl Registry {
+    /// Create a registry with the standard Xapian subclasses registered
+    pub fn new() -> Result<Self, Error> {
+        Ok(Self { cxxp: ffi::new_registry()? })
+    }
+}
+
+/// Rust iterator over a range of Xapian terms, e.g. the terms of a [`Query`]
+pub struct TermIter {
+    begin: TermIterator,
+    end: TermIterator,
+    // set once advancing fails, the position is unknown after that
+    done: bool,
+}
+
+impl TermIter {
+    fn new(begin: UniquePtr<ffi::TermIterator>, end: UniquePtr<ffi::TermIterator>) -> Self {
+        Self {
+            begin: TermIterator { cxxp: begin },
+            end: TermIterator { cxxp: end },
+            done: false,
+        }
+    }
+}
+
+impl Iterator for TermIter {
+    type Item = Result<String, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done || self.begin.eq(&mut self.end) {
+            return None;
+        }
+        let term = self.begin.get_term();
+        if let Err(e) = self.begin.next() {
+            self.done = true;
+            return Some(Err(e));
+        }
+        Some(term)
+    }
+}
+
+pub use ffi::WeightStats;
+
+/// A weighting scheme implemented in rust, see [`Enquire::set_weighting_scheme`]
+///
+/// Xapian clones the scheme for each term in the query, then calls [`Weight::init`] on each clone
+/// with the statistics for that term. A clone is also used for the term-independent extra part.
+pub trait Weight: WeightClone {
+    /// The statistics needed by this scheme, zero or more of [`constants::WeightStat`] OR-ed together
+    fn stats_needed(&self) -> i32;
+
+    /// Initialise with the statistics for the current term, `factor` is the query's scale factor
+    ///
+    /// Only the statistics asked for by [`Weight::stats_needed`] are filled in, the rest are zero.
+    fn init(&mut self, factor: f64, stats: &WeightStats);
+
+    /// Calculate the weight contribution of the current term to a document
+    fn get_sumpart(&self, wdf: u32, doclen: u32, uniqterms: u32) -> f64;
+
+    /// Return an upper bound on what [`Weight::get_sumpart`] can return for any document
+    fn get_maxpart(&self) -> f64;
+
+    /// Calculate the term-independent weight component for a document
+    fn get_sumextra(&self, _doclen: u32, _uniqterms: u32) -> f64 {
+        0.0
+    }
+
+    /// Return an upper bound on what [`Weight::get_sumextra`] can return for any document
+    fn get_maxextra(&self) -> f64 {
+        0.0
+    }
+}
+
+/// Lets a boxed [`Weight`] be cloned, implemented for every `Weight` which is `Clone`
+pub trait WeightClone {
+    fn clone_box(&self) -> Box<dyn Weight>;
+}
+
+impl<T: Weight + Clone + 'static> WeightClone for T {
+    fn clone_box(&self) -> Box<dyn Weight> {
+        Box::new(self.clone())
+    }
+}
+
+struct WeightCallback {
+    weight: Box<dyn Weight>,
+}
+
+fn weight_stats_needed(callback: &WeightCallback) -> Result<i32, String> {
+    catch_callback_panic("Weight", || callback.weight.stats_needed())
+}
+
+fn weight_clone(callback: &WeightCallback) -> Result<Box<WeightCallback>, String> {
+    catch_callback_panic("Weight", || {
+        Box::new(WeightCallback {
+            weight: callback.weight.clone_box(),
+        })
+    })
+}
+
+fn weight_init(callback: &mut WeightCallback, factor: f64, stats: &WeightStats) -> Result<(), String> {
+    catch_callback_panic("Weight", || callback.weight.init(factor, stats))
+}
+
+fn weight_get_sumpart(callback: &WeightCallback, wdf: u32, doclen: u32, uniqterms: u32) -> Result<f64, String> {
+    catch_callback_panic("Weight", || callback.weight.get_sumpart(wdf, doclen, uniqterms))
+}
+
+fn weight_get_maxpart(callback: &WeightCallback) -> Result<f64, String> {
+    catch_callback_panic("Weight", || callback.weight.get_maxpart())
+}
+
+fn weight_get_sumextra(callback: &WeightCallback, doclen: u32, uniqterms: u32) -> Result<f64, String> {
+    catch_callback_panic("Weight", || callback.weight.get_sumextra(doclen, uniqterms))
+}
+
+fn weight_get_maxextra(callback: &WeightCallback) -> Result<f64, String> {
+    catch_callback_panic("Weight", || callback.weight.get_maxextra())
+}
+
+/// A weighting scheme which can be passed to [`Enquire::set_weighting_scheme`]
+///
+/// Implemented by the built-in schemes below and by every custom [`Weight`].
+pub trait WeightScheme {
+    #[doc(hidden)]
+    fn set_on(self, enquire: &mut Enquire) -> Result<(), Error>;
+}
+
+impl<T: Weight + 'static> WeightScheme for T {
+    fn set_on(self, enquire: &mut Enquire) -> Result<(), Error> {
+        let callback = Box::new(WeightCallback { weight: Box::new(self) });
+        ffi::enquire_set_weighting_scheme_custom(enquire.cxxp.pin_mut(), callback)?;
+        Ok(())
+    }
+}
+
+macro_rules! impl_builtin_weight_scheme {
+    ($($t:ident),*) => {
+        $(
+            impl WeightScheme for $t {
+                fn set_on(self, enquire: &mut Enquire) -> Result<(), Error> {
+                    ffi::enquire_set_weighting_scheme(enquire.cxxp.pin_mut(), &self.cxxp)?;
+                    Ok(())
+                }
+            }
+        )*
+    };
+}
+
+impl_builtin_weight_scheme!(
+    BoolWeight,
+    TfIdfWeight,
+    BM25Weight,
+    BM25PlusWeight,
+    InL2Weight,
+    IfB2Weight,
+    IneB2Weight,
+    BB2Weight,
+    DLHWeight,
+    PL2Weight,
+    PL2PlusWeight,
+    DPHWeight,
+    LMWeight,
+    CoordWeight,
+    DiceCoeffWeight
+);
+
+/// Weights every matching document 0, i.e. pure boolean matching
+pub struct BoolWeight {
+    pub cxxp: UniquePtr<ffi::Weight>,
+}
+
+impl BoolWeight {
+    pub fn new() -> Result<Self, Error> {
+        Ok(Self {
+            cxxp: ffi::new_bool_weight()?,
+        })
+    }
+}
+
+/// Parameters of [`TfIdfWeight`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct TfIdfParams {
+    /// Three characters selecting the wdf, idf and weight normalizations, e.g. "ntn"
+    pub normalizations: String,
+}
+
+impl Default for TfIdfParams {
+    fn default() -> Self {
+        Self {
+            normalizations: "ntn".to_string(),
+        }
+    }
+}
+
+/// The TF-IDF weighting scheme
+pub struct TfIdfWeight {
+    pub cxxp: UniquePtr<ffi::Weight>,
+}
+
+impl TfIdfWeight {
+    pub fn new(params: TfIdfParams) -> Result<Self, Error> {
+        Ok(Self {
+            cxxp: ffi::new_tfidf_weight(&params.normalizations)?,
+        })
+    }
+}
+
+/// Parameters of [`BM25Weight`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct BM25Params {
+    /// How strongly the wdf is taken into account
+    pub k1: f64,
+    /// How strongly the document length is taken into account in the extra part
+    pub k2: f64,
+    /// How strongly the wqf is taken into account
+    pub k3: f64,
+    /// How strongly the document length is normalised, between 0 and 1
+    pub b: f64,
+    /// Lower bound on the normalised document length
+    pub min_normlen: f64,
+}
+
+impl Default for BM25Params {
+    fn default() -> Self {
+        Self {
+            k1: 1.0,
+            k2: 0.0,
+            k3: 1.0,
+            b: 0.5,
+            min_normlen: 0.5,
+        }
+    }
+}
+
+/// The BM25 weighting scheme, the default used by [`Enquire`]
+pub struct BM25Weight {
+    pub cxxp: UniquePtr<ffi::Weight>,
+}
+
+impl BM25Weight {
+    pub fn new(params: BM25Params) -> Result<Self, Error> {
+        Ok(Self {
+            cxxp: ffi::new_bm25_weight(params.k1, params.k2, params.k3, params.b, params.min_normlen)?,
+        })
+    }
+}
+
+/// Parameters of [`BM25PlusWeight`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct BM25PlusParams {
+    pub k1: f64,
+    pub k2: f64,
+    pub k3: f64,
+    pub b: f64,
+    pub min_normlen: f64,
+    /// Lower bound on the weight of a matching term, so long documents aren't over-penalised
+    pub delta: f64,
+}
+
+impl Default for BM25PlusParams {
+    fn default() -> Self {
+        Self {
+            k1: 1.0,
+            k2: 0.0,
+            k3: 1.0,
+            b: 0.5,
+            min_normlen: 0.5,
+            delta: 1.0,
+        }
+    }
+}
+
+/// The BM25+ weighting scheme
+pub struct BM25PlusWeight {
+    pub cxxp: UniquePtr<ffi::Weight>,
+}
+
+impl BM25PlusWeight {
+    pub fn new(params: BM25PlusParams) -> Result<Self, Error> {
+        Ok(Self {
+            cxxp: ffi::new_bm25plus_weight(params.k1, params.k2, params.k3, params.b, params.min_normlen, params.delta)?,
+        })
+    }
+}
+
+/// Parameters of the divergence from randomness schemes [`InL2Weight`], [`IfB2Weight`],
+/// [`IneB2Weight`], [`BB2Weight`] and [`PL2Weight`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct DfrParams {
+    /// The wdf normalization parameter
+    pub c: f64,
+}
+
+impl Default for DfrParams {
+    fn default() -> Self {
+        Self { c: 1.0 }
+    }
+}
+
+/// The InL2 divergence from randomness weighting scheme
+pub struct InL2Weight {
+    pub cxxp: UniquePtr<ffi::Weight>,
+}
+
+impl InL2Weight {
+    pub fn new(params: DfrParams) -> Result<Self, Error> {
+        Ok(Self {
+            cxxp: ffi::new_inl2_weight(params.c)?,
+        })
+    }
+}
+
+/// The IfB2 divergence from randomness weighting scheme
+pub struct IfB2Weight {
+    pub cxxp: UniquePtr<ffi::Weight>,
+}
+
+impl IfB2Weight {
+    pub fn new(params: DfrParams) -> Result<Self, Error> {
+        Ok(Self {
+            cxxp: ffi::new_ifb2_weight(params.c)?,
+        })
+    }
+}
+
+/// The IneB2 divergence from randomness weighting scheme
+pub struct IneB2Weight {
+    pub cxxp: UniquePtr<ffi::Weight>,
+}
+
+impl IneB2Weight {
+    pub fn new(params: DfrParams) -> Result<Self, Error> {
+        Ok(Self {
+            cxxp: ffi::new_ineb2_weight(params.c)?,
+        })
+    }
+}
+
+/// The BB2 divergence from randomness weighting scheme
+pub struct BB2Weight {
+    pub cxxp: UniquePtr<ffi::Weight>,
+}
+
+impl BB2Weight {
+    pub fn new(params: DfrParams) -> Result<Self, Error> {
+        Ok(Self {
+            cxxp: ffi::new_bb2_weight(params.c)?,
+        })
+    }
+}
+
+/// The parameter free DLH divergence from randomness weighting scheme
+pub struct DLHWeight {
+    pub cxxp: UniquePtr<ffi::Weight>,
+}
+
+impl DLHWeight {
+    pub fn new() -> Result<Self, Error> {
+        Ok(Self {
+            cxxp: ffi::new_dlh_weight()?,
+        })
+    }
+}
+
+/// The PL2 divergence from randomness weighting scheme
+pub struct PL2Weight {
+    pub cxxp: UniquePtr<ffi::Weight>,
+}
+
+impl PL2Weight {
+    pub fn new(params: DfrParams) -> Result<Self, Error> {
+        Ok(Self {
+            cxxp: ffi::new_pl2_weight(params.c)?,
+        })
+    }
+}
+
+/// Parameters of [`PL2PlusWeight`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct PL2PlusParams {
+    /// The wdf normalization parameter
+    pub c: f64,
+    /// Lower bound on the weight of a matching term
+    pub delta: f64,
+}
+
+impl Default for PL2PlusParams {
+    fn default() -> Self {
+        Self { c: 1.0, delta: 0.8 }
+    }
+}
+
+/// The PL2+ divergence from randomness weighting scheme
+pub struct PL2PlusWeight {
+    pub cxxp: UniquePtr<ffi::Weight>,
+}
+
+impl PL2PlusWeight {
+    pub fn new(params: PL2PlusParams) -> Result<Self, Error> {
+        Ok(Self {
+            cxxp: ffi::new_pl2plus_weight(params.c, params.delta)?,
+        })
+    }
+}
+
+/// The parameter free DPH divergence from randomness weighting scheme
+pub struct DPHWeight {
+    pub cxxp: UniquePtr<ffi::Weight>,
+}
+
+impl DPHWeight {
+    pub fn new() -> Result<Self, Error> {
+        Ok(Self {
+            cxxp: ffi::new_dph_weight()?,
+        })
+    }
+}
+
+/// Parameters of [`LMWeight`], negative smoothing parameters select xapian's defaults
+#[derive(Debug, Clone, PartialEq)]
+pub struct LMParams {
+    /// Added to the weight to avoid negative logs, 0 uses the document length upper bound
+    pub param_log: f64,
+    pub smoothing: constants::LMSmoothing,
+    pub smoothing1: f64,
+    pub smoothing2: f64,
+}
+
+impl Default for LMParams {
+    fn default() -> Self {
+        Self {
+            param_log: 0.0,
+            smoothing: constants::LMSmoothing::TwoStage,
+            smoothing1: -1.0,
+            smoothing2: -1.0,
+        }
+    }
+}
+
+/// The unigram language modelling weighting scheme
+pub struct LMWeight {
+    pub cxxp: UniquePtr<ffi::Weight>,
+}
+
+impl LMWeight {
+    pub fn new(params: LMParams) -> Result<Self, Error> {
+        Ok(Self {
+            cxxp: ffi::new_lm_weight(params.param_log, params.smoothing as i32, params.smoothing1, params.smoothing2)?,
+        })
+    }
+}
+
+/// Weights each matching term 1, so documents are ranked by how many query terms they match
+pub struct CoordWeight {
+    pub cxxp: UniquePtr<ffi::Weight>,
+}
+
+impl CoordWeight {
+    pub fn new() -> Result<Self, Error> {
+        Ok(Self {
+            cxxp: ffi::new_coord_weight()?,
+        })
+    }
+}
+
+/// Ranks documents by the Dice coefficient of the document and query terms, requires xapian 1.5
+pub struct DiceCoeffWeight {
+    pub cxxp: UniquePtr<ffi::Weight>,
+}
+
+impl DiceCoeffWeight {
+    pub fn new() -> Result<Self, Error> {
+        Ok(Self {
+            cxxp: ffi::new_dice_coeff_weight()?,
+        })
+    }
+}
+
+/// A source of documents and weights implemented in rust, see [`Query::from_posting_source`]
+///
+/// Xapian clones the source for each database it searches, then calls [`PostingSource::init`]
+/// on the clone before iterating over it in ascending docid order.
+pub trait PostingSource: PostingSourceClone {
+    /// Prepare to iterate over the documents of `db`, called before any other method
+    fn init(&mut self, db: &mut Database);
+
+    /// A lower bound on the number of documents this source returns
+    fn get_termfreq_min(&self) -> u32;
+
+    /// An estimate of the number of documents this source returns
+    fn get_termfreq_est(&self) -> u32;
+
+    /// An upper bound on the number of documents this source returns
+    fn get_termfreq_max(&self) -> u32;
+
+    /// An upper bound on what [`PostingSource::get_weight`] returns, checked after each move
+    fn get_maxweight(&self) -> f64 {
+        0.0
+    }
+
+    /// The weight contributed by the current document
+    fn get_weight(&self) -> f64 {
+        0.0
+    }
+
+    /// The current document
+    fn get_docid(&self) -> u32;
+
+    /// Advance to the next document, documents with a weight below `min_wt` may be skipped
+    fn next(&mut self, min_wt: f64);
+
+    /// Advance to the first document with a docid of at least `did`
+    fn skip_to(&mut self, did: u32, min_wt: f64) {
+        while !self.at_end() && self.get_docid() < did {
+            self.next(min_wt);
+        }
+    }
+
+    /// Check whether `did` is in this source, either moving to it and returning true, or
+    /// returning false if it is cheaper to just report whether it is there
+    fn check(&mut self, did: u32, min_wt: f64) -> bool {
+        self.skip_to(did, min_wt);
+        true
+    }
+
+    /// Whether the source has moved past its last document
+    fn at_end(&self) -> bool;
+}
+
+/// Lets a boxed [`PostingSource`] be cloned, implemented for every `PostingSource` which is `Clone`
+pub trait PostingSourceClone {
+    fn clone_box(&self) -> Box<dyn PostingSource>;
+}
+
+impl<T: PostingSource + Clone + 'static> PostingSourceClone for T {
+    fn clone_box(&self) -> Box<dyn PostingSource> {
+        Box::new(self.clone())
+    }
+}
+
+struct PostingSourceCallback {
+    source: Box<dyn PostingSource>,
+}
+
+/// A posting source which can be passed to [`Query::from_posting_source`]
+///
+/// Implemented by the built-in sources below and by every custom [`PostingSource`].
+pub trait IntoPostingSource {
+    #[doc(hidden)]
+    fn into_query(self) -> Result<Query, Error>;
+}
+
+impl<T: PostingSource + 'static> IntoPostingSource for T {
+    fn into_query(self) -> Result<Query, Error> {
+        let callback = Box::new(PostingSourceCallback { source: Box::new(self) });
+        Ok(Query {
+            cxxp: ffi::new_query_from_posting_source(callback)?,
+        })
+    }
+}
+
+macro_rules! impl_builtin_posting_source {
+    ($($t:ident),*) => {
+        $(
+            impl IntoPostingSource for $t {
+                fn into_query(self) -> Result<Query, Error> {
+                    Ok(Query {
+                        cxxp: ffi::new_query_from_builtin_posting_source(self.cxxp)?,
+                    })
+                }
+            }
+        )*
+    };
+}
+
+impl_builtin_posting_source!(
+    ValueWeightPostingSource,
+    DecreasingValueWeightPostingSource,
+    FixedWeightPostingSource,
+    LatLongDistancePostingSource
+);
+
+/// Weights documents by the value in a slot, which must have been stored with `sortable_serialise`
+/// (e.g. [`Document::add_double`])
+pub struct ValueWeightPostingSource {
+    pub cxxp: UniquePtr<ffi::PostingSource>,
+}
+
+impl ValueWeightPostingSource {
+    pub fn new(slot: u32) -> Result<Self, Error> {
+        Ok(Self {
+            cxxp: ffi::new_value_weight_posting_source(slot)?,
+        })
+    }
+}
+
+/// Like [`ValueWeightPostingSource`], for values which decrease with the docid in the range
+/// `range_start..=range_end` (0 meaning all documents), which lets the matcher stop early
+pub struct DecreasingValueWeightPostingSource {
+    pub cxxp: UniquePtr<ffi::PostingSource>,
+}
+
+impl DecreasingValueWeightPostingSource {
+    pub fn new(slot: u32, range_start: u32, range_end: u32) -> Result<Self, Error> {
+        Ok(Self {
+            cxxp: ffi::new_decreasing_value_weight_posting_source(slot, range_start, range_end)?,
+        })
+    }
+}
+
+/// Weights documents by looking up the value in a slot in a map
+pub struct ValueMapPostingSource {
+    pub cxxp: UniquePtr<ffi::ValueMapPostingSource>,
+}
+
+impl ValueMapPostingSource {
+    pub fn new(slot: u32) -> Result<Self, Error> {
+        Ok(Self {
+            cxxp: ffi::new_value_map_posting_source(slot)?,
+        })
+    }
+
+    /// Build a source with a weight for each value in `mappings`, and `default_weight` for other values
+    pub fn from_map(slot: u32, mappings: &HashMap<String, f64>, default_weight: f64) -> Result<Self, Error> {
+        let mut source = Self::new(slot)?;
+        for (key, wt) in mappings {
+            source.add_mapping(key, *wt)?;
+        }
+        source.set_default_weight(default_weight)?;
+        Ok(source)
+    }
+
+    pub fn add_mapping(&mut self, key: &str, wt: f64) -> Result<(), Error> {
+        ffi::value_map_posting_source_add_mapping(self.cxxp.pin_mut(), key, wt)?;
+        Ok(())
+    }
+
+    pub fn clear_mappings(&mut self) -> Result<(), Error> {
+        ffi::value_map_posting_source_clear_mappings(self.cxxp.pin_mut())?;
+        Ok(())
+    }
+
+    /// Set the weight of documents whose value is not in the map, 0 by default
+    pub fn set_default_weight(&mut self, wt: f64) -> Result<(), Error> {
+        ffi::value_map_posting_source_set_default_weight(self.cxxp.pin_mut(), wt)?;
+        Ok(())
     }
 }
 
-pub struct NumberRangeProcessor {
-    pub cxxp: UniquePtr<ffi::NumberRangeProcessor>,
+impl IntoPostingSource for ValueMapPostingSource {
+    fn into_query(self) -> Result<Query, Error> {
+        Ok(Query {
+            cxxp: ffi::new_query_from_value_map_posting_source(self.cxxp)?,
+        })
+    }
 }
 
-impl NumberRangeProcessor {
-    pub fn new(slot: u32, prefix: &str, flags: crate::constants::RangeProcessorFlags) -> Result<Self, cxx::Exception> {
+/// Gives every document in the database the same weight
+pub struct FixedWeightPostingSource {
+    pub cxxp: UniquePtr<ffi::PostingSource>,
+}
+
+impl FixedWeightPostingSource {
+    pub fn new(wt: f64) -> Result<Self, Error> {
         Ok(Self {
-            cxxp: ffi::new_number_range_processor(slot, prefix, flags as i32)?,
+            cxxp: ffi::new_fixed_weight_posting_source(wt)?,
         })
     }
 }
 
-#[warn(unused_unsafe)]
-pub struct TermIterator {
-    pub cxxp: UniquePtr<ffi::TermIterator>,
+fn posting_source_init(callback: &mut PostingSourceCallback, db: UniquePtr<ffi::Database>) -> Result<(), String> {
+    catch_callback_panic("PostingSource", || callback.source.init(&mut Database { cxxp: db }))
 }
 
-// std::string &term_iterator_get_termfreq_value(TermIterator &titer, int8_t &err);
-// int term_iterator_get_termfreq_freq(TermIterator &titer, int8_t &err);
-// bool term_iterator_eq(TermIterator &titer, TermIterator &other, int8_t &err);
-impl TermIterator {
-    pub fn get_termfreq_value(&mut self) -> String {
-        let rs = ffi::term_iterator_get_termfreq_value(self.cxxp.pin_mut());
-        return rs.to_string();
+fn posting_source_get_termfreq_min(callback: &PostingSourceCallback) -> Result<u32, String> {
+    catch_callback_panic("PostingSource", || callback.source.get_termfreq_min())
+}
+
+fn posting_source_get_termfreq_est(callback: &PostingSourceCallback) -> Result<u32, String> {
+    catch_callback_panic("PostingSource", || callback.source.get_termfreq_est())
+}
+
+fn posting_source_get_termfreq_max(callback: &PostingSourceCallback) -> Result<u32, String> {
+    catch_callback_panic("PostingSource", || callback.source.get_termfreq_max())
+}
+
+fn posting_source_get_maxweight(callback: &PostingSourceCallback) -> Result<f64, String> {
+    catch_callback_panic("PostingSource", || callback.source.get_maxweight())
+}
+
+fn posting_source_get_weight(callback: &PostingSourceCallback) -> Result<f64, String> {
+    catch_callback_panic("PostingSource", || callback.source.get_weight())
+}
+
+fn posting_source_get_docid(callback: &PostingSourceCallback) -> Result<u32, String> {
+    catch_callback_panic("PostingSource", || callback.source.get_docid())
+}
+
+fn posting_source_next(callback: &mut PostingSourceCallback, min_wt: f64) -> Result<(), String> {
+    catch_callback_panic("PostingSource", || callback.source.next(min_wt))
+}
+
+fn posting_source_skip_to(callback: &mut PostingSourceCallback, did: u32, min_wt: f64) -> Result<(), String> {
+    catch_callback_panic("PostingSource", || callback.source.skip_to(did, min_wt))
+}
+
+fn posting_source_check(callback: &mut PostingSourceCallback, did: u32, min_wt: f64) -> Result<bool, String> {
+    catch_callback_panic("PostingSource", || callback.source.check(did, min_wt))
+}
+
+fn posting_source_at_end(callback: &PostingSourceCallback) -> Result<bool, String> {
+    catch_callback_panic("PostingSource", || callback.source.at_end())
+}
+
+fn posting_source_clone(callback: &PostingSourceCallback) -> Result<Box<PostingSourceCallback>, String> {
+    catch_callback_panic("PostingSource", || {
+        Box::new(PostingSourceCallback {
+            source: callback.source.clone_box(),
+        })
+    })
+}
+
+/// A latitude-longitude coordinate in degrees
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LatLongCoord {
+    pub latitude: f64,
+    pub longitude: f64,
+}
+
+impl LatLongCoord {
+    pub fn new(latitude: f64, longitude: f64) -> Self {
+        Self { latitude, longitude }
     }
+}
 
-    pub fn get_termfreq_freq(&mut self) -> i32 {
-        let rs = ffi::term_iterator_get_termfreq_freq(self.cxxp.pin_mut());
-        return rs;
+/// A set of coordinates, stored serialised in a document value slot for geospatial search
+pub struct LatLongCoords {
+    pub cxxp: UniquePtr<ffi::LatLongCoords>,
+}
+
+impl LatLongCoords {
+    pub fn new() -> Result<Self, Error> {
+        Ok(Self {
+            cxxp: ffi::new_lat_long_coords()?,
+        })
     }
 
-    pub fn eq(&mut self, other: &mut TermIterator) -> bool {
-        let rs = ffi::term_iterator_eq(self.cxxp.pin_mut(), other.cxxp.pin_mut());
-        return rs;
+    pub fn from_coords(coords: impl IntoIterator<Item = LatLongCoord>) -> Result<Self, Error> {
+        let mut this = Self::new()?;
+        for coord in coords {
+            this.append(coord)?;
+        }
+        Ok(this)
+    }
+
+    /// Add a coordinate, the latitude must be between -90 and 90
+    pub fn append(&mut self, coord: LatLongCoord) -> Result<(), Error> {
+        ffi::lat_long_coords_append(self.cxxp.pin_mut(), coord.latitude, coord.longitude)?;
+        Ok(())
+    }
+
+    pub fn size(&self) -> usize {
+        ffi::lat_long_coords_size(&self.cxxp)
+    }
+
+    pub fn coords(&self) -> Result<Vec<LatLongCoord>, Error> {
+        let flat = ffi::lat_long_coords_flatten(&self.cxxp)?;
+        Ok(flat.chunks(2).map(|c| LatLongCoord::new(c[0], c[1])).collect())
+    }
+
+    /// Serialise to a value for [`Document::add_value_bytes`]
+    pub fn serialise(&self) -> Result<Vec<u8>, Error> {
+        Ok(ffi::lat_long_coords_serialise(&self.cxxp)?)
     }
 
-    pub fn next(&mut self) {
-        ffi::term_iterator_next(self.cxxp.pin_mut());
+    pub fn unserialise(data: &[u8]) -> Result<Self, Error> {
+        let mut this = Self::new()?;
+        ffi::lat_long_coords_unserialise(this.cxxp.pin_mut(), data)?;
+        Ok(this)
     }
 }
 
-pub struct BoolWeight {
-    pub cxxp: UniquePtr<ffi::BoolWeight>,
+/// Measures distances along the surface of a sphere, by default the earth, in metres
+pub struct GreatCircleMetric {
+    pub cxxp: UniquePtr<ffi::GreatCircleMetric>,
 }
 
-impl BoolWeight {
-    pub fn new() -> Result<Self, cxx::Exception> {
+impl GreatCircleMetric {
+    pub fn new() -> Result<Self, Error> {
         Ok(Self {
-            cxxp: ffi::new_bool_weight()?,
+            cxxp: ffi::new_great_circle_metric()?,
+        })
+    }
+
+    /// Use a sphere of `radius` metres
+    pub fn with_radius(radius: f64) -> Result<Self, Error> {
+        Ok(Self {
+            cxxp: ffi::new_great_circle_metric_with_radius(radius)?,
         })
     }
+
+    /// The minimum distance between any coordinate of `a` and any coordinate of `b`
+    pub fn distance(&self, a: &LatLongCoords, b: &LatLongCoords) -> Result<f64, Error> {
+        Ok(ffi::great_circle_metric_distance(&self.cxxp, &a.cxxp, &b.cxxp)?)
+    }
 }
 
-pub struct BM25Weight {
-    pub cxxp: UniquePtr<ffi::BM25Weight>,
+/// Matches the documents within `max_range` of `centre` (0 meaning no limit), weighting closer ones higher
+///
+/// The weight is `k1 * (distance + k1) ^ -k2`, the default `k1` is 1000 and `k2` is 1.
+pub struct LatLongDistancePostingSource {
+    pub cxxp: UniquePtr<ffi::PostingSource>,
 }
 
-impl BM25Weight {
-    pub fn new(k1: f64, k2: f64, k3: f64, b: f64, min_normlen: f64) -> Result<Self, cxx::Exception> {
+impl LatLongDistancePostingSource {
+    pub fn new(slot: u32, centre: &LatLongCoords, metric: &GreatCircleMetric, max_range: f64, k1: f64, k2: f64) -> Result<Self, Error> {
+        Ok(Self {
+            cxxp: ffi::new_lat_long_distance_posting_source(slot, &centre.cxxp, &metric.cxxp, max_range, k1, k2)?,
+        })
+    }
+}
+
+/// Generates sort keys from the distance to `centre`, so results can be sorted nearest first
+pub struct LatLongDistanceKeyMaker {
+    pub cxxp: UniquePtr<ffi::LatLongDistanceKeyMaker>,
+}
+
+impl LatLongDistanceKeyMaker {
+    /// Documents without coordinates get the key for `defdistance`, or sort last if it is `None`
+    pub fn new(slot: u32, centre: &LatLongCoords, metric: &GreatCircleMetric, defdistance: Option<f64>) -> Result<Self, Error> {
         Ok(Self {
-            cxxp: ffi::new_bm25_weight(k1, k2, k3, b, min_normlen)?,
+            cxxp: ffi::new_lat_long_distance_key_maker(slot, &centre.cxxp, &metric.cxxp, defdistance.is_some(), defdistance.unwrap_or(0.0))?,
         })
     }
 }
 
+impl KeyMaker for LatLongDistanceKeyMaker {
+    fn set_on(mut self, enquire: &mut Enquire, reverse: bool) -> Result<(), Error> {
+        ffi::enquire_set_sort_by_lat_long_distance_key(enquire.cxxp.pin_mut(), self.cxxp.pin_mut(), reverse)?;
+        enquire.sorter = Some(Box::new(self));
+        Ok(())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    // a glass database with a document for each value, each indexed by the term "doc" and with
+    // the value in slot 0, opened for searching
+    fn database_with_values(dir: &tempfile::TempDir, values: &[&str]) -> Database {
+        let path = dir.path().to_str().unwrap();
+        let mut db = WritableDatabase::new(path, constants::DB_CREATE_OR_OPEN, constants::DB_BACKEND_GLASS).unwrap();
+        for value in values {
+            let mut doc = Document::new().unwrap();
+            doc.add_term("doc", 1).unwrap();
+            doc.add_value_bytes(0, value.as_bytes()).unwrap();
+            db.add_document(&mut doc).unwrap();
+        }
+        db.commit().unwrap();
+        Database::new_with_path(path, 0).unwrap()
+    }
+
     #[test]
     fn test_new_bool_weight() {
         let mut bool_weight = BoolWeight::new();
@@ -767,7 +3740,699 @@ mod tests {
     // test new bm25
     #[test]
     fn test_new_bm25_weight() {
-        let mut bm25_weight = BM25Weight::new(1.2, 1.2, 1.2, 1.2, 1.2);
+        let bm25_weight = BM25Weight::new(BM25Params { k1: 1.2, k2: 1.2, k3: 1.2, b: 1.2, min_normlen: 1.2 });
         assert!(bm25_weight.is_ok());
     }
+
+    #[test]
+    fn test_new_builtin_weights() {
+        assert!(TfIdfWeight::new(TfIdfParams::default()).is_ok());
+        assert!(TfIdfWeight::new(TfIdfParams { normalizations: "xyz".to_string() }).is_err());
+        assert!(BM25Weight::new(BM25Params { k1: 1.2, ..Default::default() }).is_ok());
+        assert!(BM25PlusWeight::new(BM25PlusParams::default()).is_ok());
+        assert!(InL2Weight::new(DfrParams::default()).is_ok());
+        assert!(PL2PlusWeight::new(PL2PlusParams::default()).is_ok());
+        assert!(LMWeight::new(LMParams { smoothing: constants::LMSmoothing::Dirichlet, ..Default::default() }).is_ok());
+        assert!(CoordWeight::new().is_ok());
+    }
+
+    #[test]
+    fn test_new_combine_query() {
+        let terms = ["hello", "world"].iter().map(|t| Query::new_term(t, 1, 0).unwrap());
+        let mut phrase = Query::new_phrase(terms, 0).unwrap();
+        assert_eq!(phrase.get_description(), "Query((hello PHRASE 2 world))");
+
+        let mut empty = Query::new_combine(constants::XapianOp::OpOr, Vec::new(), 0).unwrap();
+        assert!(empty.is_empty_content_query());
+    }
+
+    #[test]
+    fn test_query_ops() {
+        let a = Query::new_term("a", 1, 0).unwrap();
+        let b = Query::new_term("b", 1, 0).unwrap();
+        assert_eq!((&a & &b).get_description(), "Query((a AND b))");
+        assert_eq!((&a | &b).get_description(), "Query((a OR b))");
+        assert_eq!((&a ^ &b).get_description(), "Query((a XOR b))");
+        assert_eq!((&a - &b).get_description(), "Query((a AND_NOT b))");
+        assert_eq!(a.scale_weight(2.0).unwrap().get_description(), "Query(2 * a)");
+        assert!(matches!(a.scale_weight(-1.0), Err(Error::InvalidArgument(_))));
+    }
+
+    #[test]
+    fn test_query_serialise_round_trip() {
+        let mut q = Query::new_term("hello", 2, 1).unwrap() | Query::new_term("world", 1, 2).unwrap();
+        let serialised = q.serialise().unwrap();
+        let mut unserialised = Query::unserialise(&serialised, None).unwrap();
+        assert_eq!(unserialised.get_description(), q.get_description());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_query_serde_round_trip() {
+        let q = Query::new_term("hello", 1, 0).unwrap() & Query::new_term("world", 1, 0).unwrap();
+        let json = serde_json::to_string(&q).unwrap();
+        let mut q: Query = serde_json::from_str(&json).unwrap();
+        assert_eq!(q.get_description(), "Query((hello AND world))");
+    }
+
+    #[test]
+    fn test_query_to_tree() {
+        let q = Query::new_term("a", 1, 1).unwrap() & Query::new_term("b", 2, 2).unwrap();
+        assert_eq!(q.get_type(), constants::XapianOp::OpAnd);
+        assert_eq!(q.get_num_subqueries(), 2);
+        assert!(matches!(q.get_subquery(2), Err(Error::Range(_))));
+        assert!(Query::new().unwrap().get_subquery(0).is_err());
+        assert_eq!(q.get_terms().unwrap().collect::<Result<Vec<_>, _>>().unwrap(), vec!["a", "b"]);
+
+        // xapian 1.4 doesn't expose the wqf and position of a leaf
+        let leaf = |n: u32| if cfg!(feature = "xapian-1_5") { Some(n) } else { None };
+        let tree = q.to_tree().unwrap();
+        assert_eq!(
+            tree,
+            QueryNode::Op {
+                op: constants::XapianOp::OpAnd,
+                subqueries: vec![
+                    QueryNode::Term { term: "a".to_string(), wqf: leaf(1), pos: leaf(1) },
+                    QueryNode::Term { term: "b".to_string(), wqf: leaf(2), pos: leaf(2) },
+                ],
+            }
+        );
+        assert_eq!(tree.terms(), vec!["a", "b"]);
+    }
+
+    #[test]
+    fn test_document_terms_and_values() {
+        let mut doc = Document::new().unwrap();
+        doc.add_term("hello", 1).unwrap();
+        doc.add_posting("world", 1, 2).unwrap();
+        doc.add_string(3, "three").unwrap();
+        doc.add_int(1, 1972).unwrap();
+
+        let terms = doc.termlist().unwrap().collect::<Result<Vec<_>, _>>().unwrap();
+        assert_eq!(
+            terms,
+            vec![
+                TermListItem { term: "hello".to_string(), wdf: 1 },
+                TermListItem { term: "world".to_string(), wdf: 2 },
+            ]
+        );
+        assert_eq!(doc.get_value(3).unwrap(), "three");
+        let slots = doc.values().unwrap().map(|v| v.unwrap().slot).collect::<Vec<_>>();
+        assert_eq!(slots, vec![1, 3]);
+
+        doc.remove_term("hello").unwrap();
+        doc.remove_value(3).unwrap();
+        assert_eq!(doc.termlist_count().unwrap(), 1);
+        assert_eq!(doc.values_count().unwrap(), 1);
+        doc.clear_terms().unwrap();
+        doc.clear_values().unwrap();
+        assert_eq!(doc.termlist_count().unwrap(), 0);
+        assert_eq!(doc.get_docid().unwrap(), 0);
+    }
+
+    #[test]
+    fn test_term_and_value_iterators_at_end_are_errors() {
+        let mut doc = Document::new().unwrap();
+        doc.add_term("hello", 1).unwrap();
+        doc.add_string(0, "zero").unwrap();
+
+        let mut values_end = ValueIterator { cxxp: ffi::document_values_end(doc.cxxp.pin_mut()).unwrap() };
+        assert!(matches!(values_end.get_value(), Err(Error::Range(_))));
+        assert!(values_end.get_valueno().is_err());
+        assert!(values_end.next().is_err());
+
+        let mut termlist_end = TermIterator { cxxp: ffi::document_termlist_end(doc.cxxp.pin_mut()).unwrap() };
+        assert!(matches!(termlist_end.get_term(), Err(Error::Range(_))));
+        assert!(termlist_end.get_wdf().is_err());
+        assert!(termlist_end.next().is_err());
+
+        let mut keys_end = TermIterator { cxxp: ffi::new_term_iterator_end().unwrap() };
+        assert!(keys_end.get_term().is_err());
+
+        let mut spy = ValueCountMatchSpy::new(0).unwrap();
+        let mut counts_end = spy.values_end().unwrap();
+        assert!(counts_end.get_termfreq_value().is_err());
+        assert!(counts_end.get_termfreq_freq().is_err());
+    }
+
+    #[test]
+    fn test_document_binary_data_and_values() {
+        let mut doc = Document::new().unwrap();
+        let data = [0u8, 159, 146, 150, 255];
+        doc.set_data_bytes(&data).unwrap();
+        assert_eq!(doc.get_data_bytes().unwrap(), data);
+        assert!(doc.get_data().is_err());
+
+        doc.add_value_bytes(0, &data[1..]).unwrap();
+        assert_eq!(doc.get_value_bytes(0).unwrap(), &data[1..]);
+        assert!(doc.get_value_bytes(1).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_transaction_cancelled_on_drop() {
+        // the inmemory backend doesn't support transactions
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().to_str().unwrap();
+        let mut db = WritableDatabase::new(path, constants::DB_CREATE_OR_OPEN, constants::DB_BACKEND_GLASS).unwrap();
+        let mut doc = Document::new().unwrap();
+        doc.add_term("before", 1).unwrap();
+        assert_eq!(db.add_document(&mut doc).unwrap(), 1);
+
+        {
+            let mut txn = db.transaction(false).unwrap();
+            let mut doc = Document::new().unwrap();
+            doc.add_term("dropped", 1).unwrap();
+            assert_eq!(txn.add_document(&mut doc).unwrap(), 2);
+        }
+        db.commit().unwrap();
+        let mut reader = Database::new_with_path(path, 0).unwrap();
+        assert!(reader.term_exists("before").unwrap());
+        assert!(!reader.term_exists("dropped").unwrap());
+        assert!(matches!(reader.get_document(2), Err(Error::DocNotFound(_))));
+
+        let mut txn = db.transaction(false).unwrap();
+        let mut doc = Document::new().unwrap();
+        doc.add_term("committed", 1).unwrap();
+        txn.replace_document_by_docid(5, &mut doc).unwrap();
+        txn.commit().unwrap();
+        db.commit().unwrap();
+        reader.reopen().unwrap();
+        assert!(reader.term_exists("committed").unwrap());
+        assert!(reader.get_document(5).is_ok());
+    }
+
+    #[test]
+    fn test_metadata_and_synonyms() {
+        // the inmemory backend doesn't support iterating over metadata keys
+        let dir = tempfile::tempdir().unwrap();
+        let mut db = WritableDatabase::new(dir.path().to_str().unwrap(), constants::DB_CREATE_OR_OPEN, constants::DB_BACKEND_GLASS).unwrap();
+        db.set_metadata("schema_version", b"3").unwrap();
+        db.set_metadata("checksum", &[0x00, 0xff, 0x80]).unwrap();
+        assert_eq!(db.get_metadata("schema_version").unwrap(), b"3");
+        assert_eq!(db.get_metadata("checksum").unwrap(), vec![0x00, 0xff, 0x80]);
+        assert!(db.get_metadata("missing").unwrap().is_empty());
+        let keys = db.metadata_keys("").unwrap().collect::<Result<Vec<_>, _>>().unwrap();
+        assert_eq!(keys, vec!["checksum", "schema_version"]);
+
+        db.add_synonym("car", "automobile").unwrap();
+        db.add_synonym("car", "motor").unwrap();
+        db.add_synonym("bike", "bicycle").unwrap();
+        db.remove_synonym("car", "motor").unwrap();
+        db.clear_synonyms("bike").unwrap();
+        assert_eq!(db.synonyms("car").unwrap().collect::<Result<Vec<_>, _>>().unwrap(), vec!["automobile"]);
+        assert_eq!(db.synonym_keys("").unwrap().collect::<Result<Vec<_>, _>>().unwrap(), vec!["car"]);
+
+        db.add_spelling("xapian", 2).unwrap();
+        db.add_spelling("rust", 1).unwrap();
+        db.remove_spelling("rust", 1).unwrap();
+        assert_eq!(db.spellings().unwrap().collect::<Result<Vec<_>, _>>().unwrap(), vec!["xapian"]);
+    }
+
+    #[derive(Clone)]
+    struct CoordinateWeight {
+        factor: f64,
+    }
+
+    impl Weight for CoordinateWeight {
+        fn stats_needed(&self) -> i32 {
+            0
+        }
+
+        fn init(&mut self, factor: f64, _stats: &WeightStats) {
+            self.factor = factor;
+        }
+
+        fn get_sumpart(&self, _wdf: u32, _doclen: u32, _uniqterms: u32) -> f64 {
+            self.factor
+        }
+
+        fn get_maxpart(&self) -> f64 {
+            self.factor
+        }
+    }
+
+    #[test]
+    fn test_custom_weight_clones_are_initialised_separately() {
+        let callback = WeightCallback {
+            weight: Box::new(CoordinateWeight { factor: 0.0 }),
+        };
+        let mut clone = weight_clone(&callback).unwrap();
+        weight_init(&mut clone, 2.0, &WeightStats::default()).unwrap();
+        assert_eq!(weight_get_sumpart(&clone, 3, 10, 5), Ok(2.0));
+        assert_eq!(weight_get_maxextra(&clone), Ok(0.0));
+        assert_eq!(weight_get_sumpart(&callback, 3, 10, 5), Ok(0.0));
+    }
+
+    #[test]
+    fn test_custom_weight_gets_only_requested_stats() {
+        #[derive(Clone)]
+        struct TermFreqWeight {
+            seen: Rc<RefCell<Vec<WeightStats>>>,
+            weight: f64,
+        }
+
+        impl Weight for TermFreqWeight {
+            fn stats_needed(&self) -> i32 {
+                constants::WeightStat::COLLECTION_SIZE as i32 | constants::WeightStat::TERMFREQ as i32
+            }
+
+            fn init(&mut self, factor: f64, stats: &WeightStats) {
+                self.seen.borrow_mut().push(stats.clone());
+                self.weight = factor * stats.termfreq as f64;
+            }
+
+            fn get_sumpart(&self, _wdf: u32, _doclen: u32, _uniqterms: u32) -> f64 {
+                self.weight
+            }
+
+            fn get_maxpart(&self) -> f64 {
+                self.weight
+            }
+        }
+
+        let dir = tempfile::tempdir().unwrap();
+        let mut db = database_with_values(&dir, &["a", "b"]);
+        let mut enquire = db.new_enquire().unwrap();
+        enquire.set_query(&mut Query::new_term("doc", 1, 0).unwrap()).unwrap();
+        let seen = Rc::new(RefCell::new(Vec::new()));
+        enquire.set_weighting_scheme(TermFreqWeight { seen: seen.clone(), weight: 0.0 }).unwrap();
+        let mset = enquire.get_mset(0, 10).unwrap();
+        let weights = mset.into_iter().map(|m| m.map(|m| m.weight)).collect::<Result<Vec<_>, _>>().unwrap();
+        assert_eq!(weights, vec![2.0, 2.0]);
+
+        let seen = seen.borrow();
+        assert!(seen.iter().any(|stats| stats.termfreq == 2));
+        for stats in seen.iter() {
+            assert_eq!(stats.collection_size, 2);
+            assert_eq!((stats.average_length, stats.wqf, stats.total_length), (0.0, 0, 0));
+            assert_eq!((stats.doclength_lower_bound, stats.doclength_upper_bound, stats.wdf_upper_bound), (0, 0, 0));
+        }
+    }
+
+    #[derive(Clone)]
+    struct PopularitySource {
+        postings: Vec<(u32, f64)>,
+        pos: Option<usize>,
+    }
+
+    impl PostingSource for PopularitySource {
+        fn init(&mut self, _db: &mut Database) {
+            self.pos = None;
+        }
+
+        fn get_termfreq_min(&self) -> u32 {
+            self.postings.len() as u32
+        }
+
+        fn get_termfreq_est(&self) -> u32 {
+            self.postings.len() as u32
+        }
+
+        fn get_termfreq_max(&self) -> u32 {
+            self.postings.len() as u32
+        }
+
+        fn get_maxweight(&self) -> f64 {
+            self.postings.iter().map(|p| p.1).fold(0.0, f64::max)
+        }
+
+        fn get_weight(&self) -> f64 {
+            self.postings[self.pos.unwrap()].1
+        }
+
+        fn get_docid(&self) -> u32 {
+            self.pos.map_or(0, |pos| self.postings[pos].0)
+        }
+
+        fn next(&mut self, _min_wt: f64) {
+            self.pos = Some(self.pos.map_or(0, |pos| pos + 1));
+        }
+
+        fn at_end(&self) -> bool {
+            self.pos.is_some_and(|pos| pos >= self.postings.len())
+        }
+    }
+
+    #[test]
+    fn test_posting_source() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut db = database_with_values(&dir, &["a", "b", "c", "d"]);
+        let source = PopularitySource {
+            postings: vec![(1, 0.5), (3, 3.0), (4, 1.5)],
+            pos: None,
+        };
+        let mut query = Query::from_posting_source(source).unwrap();
+        assert_eq!(query.get_type(), constants::XapianOp::LeafPostingSource);
+
+        let mut enquire = db.new_enquire().unwrap();
+        enquire.set_query(&mut query).unwrap();
+        let mset = enquire.get_mset(0, 10).unwrap();
+        let matches = mset.into_iter().map(|m| m.map(|m| (m.docid, m.weight))).collect::<Result<Vec<_>, _>>().unwrap();
+        assert_eq!(matches, vec![(3, 3.0), (4, 1.5), (1, 0.5)]);
+    }
+
+    #[test]
+    fn test_builtin_posting_sources() {
+        let mut genres = HashMap::new();
+        genres.insert("documentary".to_string(), 2.0);
+        genres.insert("comedy".to_string(), 1.0);
+        let source = ValueMapPostingSource::from_map(2, &genres, 0.5).unwrap();
+        let query = Query::from_posting_source(source).unwrap();
+        assert_eq!(query.get_type(), constants::XapianOp::LeafPostingSource);
+
+        let rating = Query::from_posting_source(ValueWeightPostingSource::new(1).unwrap()).unwrap();
+        let text = Query::new_term("space", 1, 0).unwrap();
+        let boosted = text.add_right(constants::XapianOp::OpAndMaybe, &rating).unwrap();
+        assert_eq!(boosted.get_type(), constants::XapianOp::OpAndMaybe);
+    }
+
+    #[test]
+    fn test_lat_long_coords() {
+        let london = LatLongCoords::from_coords([LatLongCoord::new(51.5072, -0.1276)]).unwrap();
+        let paris = LatLongCoords::unserialise(&LatLongCoords::from_coords([LatLongCoord::new(48.8566, 2.3522)]).unwrap().serialise().unwrap()).unwrap();
+        assert_eq!(paris.coords().unwrap(), vec![LatLongCoord::new(48.8566, 2.3522)]);
+
+        let metric = GreatCircleMetric::new().unwrap();
+        let distance = metric.distance(&london, &paris).unwrap();
+        assert!((340_000.0..345_000.0).contains(&distance));
+
+        assert!(LatLongCoords::from_coords([LatLongCoord::new(91.0, 0.0)]).is_err());
+        let source = LatLongDistancePostingSource::new(0, &london, &metric, 50_000.0, 1000.0, 1.0).unwrap();
+        assert_eq!(Query::from_posting_source(source).unwrap().get_type(), constants::XapianOp::LeafPostingSource);
+    }
+
+    #[test]
+    fn test_stoppers() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("stopwords");
+        std::fs::write(&path, "the a\nof\n").unwrap();
+        let stopper = SimpleStopper::from_file(&path).unwrap();
+        assert!(stopper.is_stop("the").unwrap());
+        assert!(!stopper.is_stop("xapian").unwrap());
+        let missing = SimpleStopper::from_file(dir.path().join("missing")).err().unwrap();
+        assert!(matches!(missing, Error::Io { kind: io::ErrorKind::NotFound, .. }));
+
+        let short_words = Stopper::from_fn(|word| word.len() < 3).unwrap();
+        assert!(short_words.is_stop("an").unwrap());
+
+        let mut qp = QueryParser::new().unwrap();
+        qp.set_stopper(stopper).unwrap();
+        qp.parse_query("the lord of the rings", constants::QueryParserFeatureFlag::FLAG_DEFAULT as i32).unwrap();
+        let stopped = qp.stoplist().unwrap().collect::<Result<Vec<_>, _>>().unwrap();
+        assert!(stopped.contains(&"the".to_string()) && stopped.contains(&"of".to_string()));
+        assert!(!stopped.contains(&"lord".to_string()));
+
+        let mut tg = TermGenerator::new().unwrap();
+        tg.set_stopper(short_words).unwrap();
+        tg.set_stopper_strategy(constants::StopStrategy::StopAll).unwrap();
+    }
+
+    #[test]
+    fn test_date_and_unit_range_processors() {
+        let mut qp = QueryParser::new().unwrap();
+        let dates = DateRangeProcessor::new(1, "released:", constants::RangeProcessorFlags::RP_PREFIX, 1970).unwrap();
+        qp.add_rangeprocessor(dates, None).unwrap();
+        let us_dates = constants::RangeProcessorFlags::RP_DATE_PREFER_MDY | constants::RangeProcessorFlags::RP_SUFFIX;
+        qp.add_rangeprocessor(DateRangeProcessor::new(3, "us", us_dates, 1970).unwrap(), None).unwrap();
+
+        let flags = constants::QueryParserFeatureFlag::FLAG_DEFAULT as i32;
+        let mut query = qp.parse_query("released:2020-01-01..2021-06-30", flags).unwrap();
+        assert!(query.get_description().contains("20200101"));
+        let mut query = qp.parse_query("01/02/2020..03/04/2020us", flags).unwrap();
+        assert!(query.get_description().contains("20200102"));
+
+        if cfg!(feature = "xapian-1_5") {
+            let sizes = UnitRangeProcessor::new(2, "size:").unwrap();
+            qp.add_rangeprocessor(sizes, None).unwrap();
+            let query = qp.parse_query("size:10MB..2GB", flags).unwrap();
+            assert_eq!(query.get_type(), constants::XapianOp::OpValueRange);
+        } else {
+            assert!(UnitRangeProcessor::new(2, "size:").is_err());
+        }
+    }
+
+    #[test]
+    fn test_field_processor() {
+        struct Owner {
+            user_id: String,
+        }
+
+        impl FieldProcessor for Owner {
+            fn process(&mut self, term: &str) -> Result<Query, Error> {
+                let owner = if term == "me" { self.user_id.as_str() } else { term };
+                Query::new_term(&format!("XOWNER{}", owner), 1, 0)
+            }
+        }
+
+        let mut qp = QueryParser::new().unwrap();
+        qp.add_prefix("title", "T").unwrap();
+        let author_prefix = "A".to_string();
+        qp.add_prefix("author", &author_prefix).unwrap();
+        qp.add_boolean_prefix("owner", Owner { user_id: "42".to_string() }).unwrap();
+
+        let flags = constants::QueryParserFeatureFlag::FLAG_DEFAULT as i32;
+        let mut query = qp.parse_query("title:rust author:olly owner:me", flags).unwrap();
+        let description = query.get_description();
+        assert!(description.contains("XOWNER42"));
+        assert!(description.contains("Trust"));
+        assert!(description.contains("Aolly"));
+    }
+
+    #[test]
+    fn test_rust_range_processor() {
+        // price:$5..$20 as a range of cents in slot 3
+        struct Price;
+
+        impl RangeProcessor for Price {
+            fn check_range(&mut self, begin: &str, end: &str) -> Result<Option<Query>, Error> {
+                let cents = |s: &str| s.trim_start_matches('$').parse::<f64>().map(|d| d * 100.0);
+                match (cents(begin), cents(end)) {
+                    (Ok(begin), Ok(end)) => Query::new_range(constants::XapianOp::OpValueRange, 3, begin, end).map(Some),
+                    _ => Ok(None),
+                }
+            }
+
+            fn prefix(&self) -> &str {
+                "price:"
+            }
+        }
+
+        let mut qp = QueryParser::new().unwrap();
+        qp.add_rangeprocessor(Price, Some("price")).unwrap();
+        let flags = constants::QueryParserFeatureFlag::FLAG_DEFAULT as i32;
+        let query = qp.parse_query("price:$5..$20", flags).unwrap();
+        assert_eq!(query.get_type(), constants::XapianOp::OpValueRange);
+        assert!(qp.parse_query("price:cheap..dear", flags).is_err());
+    }
+
+    #[test]
+    fn test_handed_over_objects_outlive_their_handles() {
+        let mut qp = QueryParser::new().unwrap();
+        {
+            let years = NumberRangeProcessor::new(0, "year:", constants::RangeProcessorFlags::RP_PREFIX).unwrap();
+            qp.add_rangeprocessor(years, Some("year")).unwrap();
+        }
+        let flags = constants::QueryParserFeatureFlag::FLAG_DEFAULT as i32;
+        let query = qp.parse_query("year:1972..1999", flags).unwrap();
+        assert_eq!(query.get_type(), constants::XapianOp::OpValueRange);
+
+        let dir = tempfile::tempdir().unwrap();
+        let mut db = database_with_values(&dir, &["red", "blue", "red"]);
+        let mut enquire = db.new_enquire().unwrap();
+        enquire.set_query(&mut Query::new_term("doc", 1, 0).unwrap()).unwrap();
+        let spy = Rc::new(RefCell::new(ValueCountMatchSpy::new(0).unwrap()));
+        enquire.add_matchspy_value_count(&spy).unwrap();
+        let colours = spy.clone();
+        drop(spy);
+        let mut mset = enquire.get_mset(0, 10).unwrap();
+        assert_eq!(mset.size().unwrap(), 3);
+
+        let mut colours = colours.borrow_mut();
+        assert_eq!(colours.get_total(), 3);
+        let mut counts = Vec::new();
+        let mut end = colours.values_end().unwrap();
+        let mut it = colours.values_begin().unwrap();
+        while !it.eq(&mut end) {
+            counts.push((it.get_termfreq_value().unwrap(), it.get_termfreq_freq().unwrap()));
+            it.next().unwrap();
+        }
+        assert_eq!(counts, vec![("blue".to_string(), 1), ("red".to_string(), 2)]);
+    }
+
+    #[test]
+    fn test_add_matchspy_value_count_while_borrowed() {
+        let mut db = Database::new().unwrap();
+        let mut enquire = db.new_enquire().unwrap();
+        let spy = Rc::new(RefCell::new(ValueCountMatchSpy::new(0).unwrap()));
+        let _reading = spy.borrow();
+        assert!(matches!(enquire.add_matchspy_value_count(&spy), Err(Error::InvalidOperation(_))));
+    }
+
+    #[test]
+    fn test_value_count_spy_borrowed_or_replaced_while_matching() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut db = database_with_values(&dir, &["red", "blue"]);
+        let mut enquire = db.new_enquire().unwrap();
+        enquire.set_query(&mut Query::new_term("doc", 1, 0).unwrap()).unwrap();
+        let spy = Rc::new(RefCell::new(ValueCountMatchSpy::new(0).unwrap()));
+        enquire.add_matchspy_value_count(&spy).unwrap();
+
+        let reading = spy.borrow();
+        assert!(matches!(enquire.get_mset(0, 10), Err(Error::InvalidOperation(_))));
+        drop(reading);
+        assert!(enquire.get_mset(0, 10).is_ok());
+
+        *spy.borrow_mut() = ValueCountMatchSpy::new(1).unwrap();
+        assert!(matches!(enquire.get_mset(0, 10), Err(Error::InvalidOperation(_))));
+        let mut rset = RSet::new().unwrap();
+        assert!(matches!(enquire.get_mset_with_rset(0, 10, &mut rset), Err(Error::InvalidOperation(_))));
+        assert_eq!(spy.borrow_mut().get_total(), 0);
+    }
+
+    #[test]
+    fn test_mset_iterator_at_end_is_an_error() {
+        let mut db = Database::new().unwrap();
+        let mut enquire = db.new_enquire().unwrap();
+        enquire.set_query(&mut Query::new_match_all().unwrap()).unwrap();
+        let mut mset = enquire.get_mset(0, 10).unwrap();
+        let mut end = mset.end().unwrap();
+        assert!(matches!(end.get_docid(), Err(Error::Range(_))));
+        assert!(end.get_weight().is_err());
+        assert!(end.get_collapse_key().is_err());
+        assert!(end.next().is_err());
+    }
+
+    #[test]
+    fn test_eset_iterator_at_end_is_an_error() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut db = database_with_values(&dir, &["a", "b"]);
+        let mut enquire = db.new_enquire().unwrap();
+        enquire.set_query(&mut Query::new_term("doc", 1, 0).unwrap()).unwrap();
+        let mut rset = RSet::new().unwrap();
+        rset.add_document(1).unwrap();
+        let mut eset = enquire.get_eset(10, &mut rset, 0, None, 0.0).unwrap();
+        let mut end = eset.end().unwrap();
+        assert!(matches!(end.get_weight(), Err(Error::Range(_))));
+        assert!(end.get_term().is_err());
+        assert!(end.next().is_err());
+        let size = eset.size().unwrap();
+        assert!(matches!(ffi::eset_iterator_at(eset.cxxp.pin_mut(), size).map_err(Error::from), Err(Error::Range(_))));
+    }
+
+    #[test]
+    fn test_mset_index_out_of_range_is_an_error() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut db = database_with_values(&dir, &["a", "b"]);
+        let mut enquire = db.new_enquire().unwrap();
+        enquire.set_query(&mut Query::new_term("doc", 1, 0).unwrap()).unwrap();
+        let mut mset = enquire.get_mset(0, 10).unwrap();
+        assert_eq!(mset.iter().len(), 2);
+        assert!(matches!(ffi::mset_iterator_at(mset.cxxp.pin_mut(), 2).map_err(Error::from), Err(Error::Range(_))));
+    }
+
+    #[test]
+    fn test_expand_decider_skips_invalid_utf8() {
+        let decider = ExpandDecider::new(|term| term.starts_with('Z'));
+        assert_eq!(expand_decider_accept(&decider, b"Zrust"), Ok(true));
+        assert_eq!(expand_decider_accept(&decider, b"rust"), Ok(false));
+        assert_eq!(expand_decider_accept(&decider, b"Z\xff\xfe"), Ok(false));
+    }
+
+    #[test]
+    fn test_match_spy_reads_documents() {
+        struct SlotValues(Vec<Vec<u8>>);
+
+        impl MatchSpy for SlotValues {
+            fn observe(&mut self, doc: &mut Document, _wt: f64) {
+                self.0.push(doc.get_value_bytes(0).unwrap());
+            }
+        }
+
+        let dir = tempfile::tempdir().unwrap();
+        let mut db = database_with_values(&dir, &["red", "green", "blue"]);
+        let mut enquire = db.new_enquire().unwrap();
+        enquire.set_query(&mut Query::new_term("doc", 1, 0).unwrap()).unwrap();
+        let spy = Rc::new(RefCell::new(SlotValues(Vec::new())));
+        enquire.add_matchspy(&spy).unwrap();
+
+        enquire.get_mset(0, 10).unwrap();
+        let mut values = spy.borrow().0.clone();
+        values.sort();
+        assert_eq!(values, vec![b"blue".to_vec(), b"green".to_vec(), b"red".to_vec()]);
+
+        // borrowed by the caller while matching
+        let _borrowed = spy.borrow();
+        assert!(enquire.get_mset(0, 10).is_err());
+    }
+
+    #[test]
+    fn test_callback_panics_become_errors() {
+        assert_eq!(catch_callback_panic("Stopper", || true), Ok(true));
+        assert_eq!(catch_callback_panic::<bool>("Stopper", || panic!("no stop list")), Err("Stopper panicked: no stop list".to_string()));
+        assert_eq!(catch_callback_panic::<f64>("Weight", || panic!("negative idf for {}", "rust")), Err("Weight panicked: negative idf for rust".to_string()));
+        assert_eq!(catch_callback_panic::<()>("MatchSpy", || panic::panic_any(7)), Err("MatchSpy panicked: unknown panic".to_string()));
+
+        // thrown through Xapian as rust::Error and returned by the call which ran the callback
+        struct Panicking;
+
+        impl MatchSpy for Panicking {
+            fn observe(&mut self, _doc: &mut Document, _wt: f64) {
+                panic!("histogram overflow");
+            }
+        }
+
+        let dir = tempfile::tempdir().unwrap();
+        let mut db = database_with_values(&dir, &["a"]);
+        let mut enquire = db.new_enquire().unwrap();
+        enquire.set_query(&mut Query::new_term("doc", 1, 0).unwrap()).unwrap();
+        enquire.add_matchspy(&Rc::new(RefCell::new(Panicking))).unwrap();
+        let Err(err) = enquire.get_mset(0, 10) else { panic!("the panic in the spy was not reported") };
+        assert_eq!(err, Error::Cxx("MatchSpy panicked: histogram overflow".to_string()));
+    }
+
+    #[test]
+    fn test_match_decider_filters_by_value() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut db = database_with_values(&dir, &["public", "private", "public"]);
+        let mut enquire = db.new_enquire().unwrap();
+        enquire.set_query(&mut Query::new_term("doc", 1, 0).unwrap()).unwrap();
+
+        let rejected = Rc::new(RefCell::new(Vec::new()));
+        let decider = MatchDecider::new({
+            let rejected = rejected.clone();
+            move |doc| {
+                let public = doc.get_value_bytes(0).unwrap() == b"public";
+                if !public {
+                    rejected.borrow_mut().push(doc.get_docid().unwrap());
+                }
+                public
+            }
+        });
+        let mset = enquire.get_mset_with_decider(0, 10, 3, &decider).unwrap();
+        let mut docids = mset.into_iter().map(|m| m.map(|m| m.docid)).collect::<Result<Vec<_>, _>>().unwrap();
+        docids.sort();
+        assert_eq!(docids, vec![1, 3]);
+        assert_eq!(*rejected.borrow(), vec![2]);
+    }
+
+    #[test]
+    fn test_error_from_what() {
+        // the errno field is split into its own literal so NUL then digits isn't read as an octal escape
+        let err = Error::from_what(concat!("DatabaseModifiedError\0The revision being read has been discarded\0\0", "0\0"));
+        assert!(err.is_database_error());
+        match err {
+            Error::DatabaseModified(info) => {
+                assert_eq!(info.msg, "The revision being read has been discarded");
+                assert!(info.context.is_empty());
+                assert_eq!(info.errno, None);
+                assert_eq!(info.error_string, None);
+            }
+            _ => panic!("unexpected error: {:?}", err),
+        }
+
+        let err = Error::from_what(concat!("DatabaseLockError\0Unable to get write lock\0./data/db\0", "11\0Resource temporarily unavailable"));
+        assert_eq!(err.info().unwrap().errno, Some(11));
+        assert_eq!(err.info().unwrap().error_string.as_deref(), Some("Resource temporarily unavailable"));
+        assert_eq!(Error::from_what("std::bad_alloc"), Error::Cxx("std::bad_alloc".to_string()));
+    }
 }
\ No newline at end of file
@@ -45,6 +45,116 @@ void database_reopen(Database &db)
     db.reopen();
 }
 
+std::unique_ptr<Document> database_get_document(Database &db, docid did)
+{
+    return std::make_unique<Xapian::Document>(db.get_document(did));
+}
+
+doccount database_get_doccount(Database &db)
+{
+    return db.get_doccount();
+}
+
+docid database_get_lastdocid(Database &db)
+{
+    return db.get_lastdocid();
+}
+
+double database_get_avlength(Database &db)
+{
+    return db.get_avlength();
+}
+
+doccount database_get_termfreq(Database &db, rust::Str term)
+{
+    return db.get_termfreq(std::string(term));
+}
+
+termcount database_get_collection_freq(Database &db, rust::Str term)
+{
+    return db.get_collection_freq(std::string(term));
+}
+
+bool database_term_exists(Database &db, rust::Str term)
+{
+    return db.term_exists(std::string(term));
+}
+
+doccount database_get_value_freq(Database &db, valueno slot)
+{
+    return db.get_value_freq(slot);
+}
+
+static rust::Vec<uint8_t> to_rust_bytes(const std::string &s)
+{
+    rust::Vec<uint8_t> bytes;
+    bytes.reserve(s.size());
+    for (unsigned char c : s) {
+        bytes.push_back(c);
+    }
+    return bytes;
+}
+
+static std::string from_rust_bytes(rust::Slice<const uint8_t> bytes)
+{
+    return std::string(reinterpret_cast<const char *>(bytes.data()), bytes.size());
+}
+
+rust::Vec<uint8_t> database_get_value_lower_bound(Database &db, valueno slot)
+{
+    return to_rust_bytes(db.get_value_lower_bound(slot));
+}
+
+rust::Vec<uint8_t> database_get_value_upper_bound(Database &db, valueno slot)
+{
+    return to_rust_bytes(db.get_value_upper_bound(slot));
+}
+
+termcount database_get_doclength(Database &db, docid did)
+{
+    return db.get_doclength(did);
+}
+
+rust::String database_get_uuid(Database &db)
+{
+    return db.get_uuid();
+}
+
+uint64_t database_get_revision(Database &db)
+{
+    return db.get_revision();
+}
+
+rust::Vec<uint8_t> database_get_metadata(Database &db, rust::Str key)
+{
+    return to_rust_bytes(db.get_metadata(std::string(key)));
+}
+
+std::unique_ptr<TermIterator> database_metadata_keys_begin(Database &db, rust::Str prefix)
+{
+    return std::make_unique<Xapian::TermIterator>(db.metadata_keys_begin(std::string(prefix)));
+}
+
+std::unique_ptr<TermIterator> database_spellings_begin(Database &db)
+{
+    return std::make_unique<Xapian::TermIterator>(db.spellings_begin());
+}
+
+rust::String database_get_spelling_suggestion(Database &db, rust::Str word, uint32_t max_edit_distance)
+{
+    return db.get_spelling_suggestion(std::string(word), max_edit_distance);
+}
+
+std::unique_ptr<TermIterator> database_synonyms_begin(Database &db, rust::Str term)
+{
+    return std::make_unique<Xapian::TermIterator>(db.synonyms_begin(std::string(term)));
+}
+
+std::unique_ptr<TermIterator> database_synonym_keys_begin(Database &db, rust::Str prefix)
+{
+    return std::make_unique<Xapian::TermIterator>(db.synonym_keys_begin(std::string(prefix)));
+}
+
 std::unique_ptr<Enquire> new_enquire(Database &db)
 {
     return std::make_unique<Xapian::Enquire>(db);
@@ -89,6 +199,91 @@ void delete_document(WritableDatabase &db, rust::Str unique_term)
     db.delete_document(std::string(unique_term));
 }
 
+docid writable_database_add_document(WritableDatabase &db, Document &doc)
+{
+    return db.add_document(doc);
+}
+
+void writable_database_replace_document_by_docid(WritableDatabase &db, docid did, Document &doc)
+{
+    db.replace_document(did, doc);
+}
+
+void writable_database_delete_document_by_docid(WritableDatabase &db, docid did)
+{
+    db.delete_document(did);
+}
+
+void writable_database_begin_transaction(WritableDatabase &db, bool flushed)
+{
+    db.begin_transaction(flushed);
+}
+
+void writable_database_commit_transaction(WritableDatabase &db)
+{
+    db.commit_transaction();
+}
+
+void writable_database_cancel_transaction(WritableDatabase &db)
+{
+    db.cancel_transaction();
+}
+
+void writable_database_set_metadata(WritableDatabase &db, rust::Str key, rust::Slice<const uint8_t> metadata)
+{
+    db.set_metadata(std::string(key), from_rust_bytes(metadata));
+}
+
+rust::Vec<uint8_t> writable_database_get_metadata(WritableDatabase &db, rust::Str key)
+{
+    return to_rust_bytes(db.get_metadata(std::string(key)));
+}
+
+std::unique_ptr<TermIterator> writable_database_metadata_keys_begin(WritableDatabase &db, rust::Str prefix)
+{
+    return std::make_unique<Xapian::TermIterator>(db.metadata_keys_begin(std::string(prefix)));
+}
+
+void writable_database_add_spelling(WritableDatabase &db, rust::Str word, termcount freqinc)
+{
+    db.add_spelling(std::string(word), freqinc);
+}
+
+void writable_database_remove_spelling(WritableDatabase &db, rust::Str word, termcount freqdec)
+{
+    db.remove_spelling(std::string(word), freqdec);
+}
+
+void writable_database_add_synonym(WritableDatabase &db, rust::Str term, rust::Str synonym)
+{
+    db.add_synonym(std::string(term), std::string(synonym));
+}
+
+void writable_database_remove_synonym(WritableDatabase &db, rust::Str term, rust::Str synonym)
+{
+    db.remove_synonym(std::string(term), std::string(synonym));
+}
+
+void writable_database_clear_synonyms(WritableDatabase &db, rust::Str term)
+{
+    db.clear_synonyms(std::string(term));
+}
+
+std::unique_ptr<TermIterator> writable_database_spellings_begin(WritableDatabase &db)
+{
+    return std::make_unique<Xapian::TermIterator>(db.spellings_begin());
+}
+
+std::unique_ptr<TermIterator> writable_database_synonyms_begin(WritableDatabase &db, rust::Str term)
+{
+    return std::make_unique<Xapian::TermIterator>(db.synonyms_begin(std::string(term)));
+}
+
+std::unique_ptr<TermIterator> writable_database_synonym_keys_begin(WritableDatabase &db, rust::Str prefix)
+{
+    return std::make_unique<Xapian::TermIterator>(db.synonym_keys_begin(std::string(prefix)));
+}
+
 
 ////////////////////////////////////////////////////////////////
 
@@ -146,6 +341,17 @@ void index_double(TermGenerator &tg, double in_data, rust::Str prefix)
     tg.index_text(data, 1, std::string(prefix));
 }
 
+// the stopper is released, so the term generator owns it from now on
+void term_generator_set_stopper(TermGenerator &tg, std::unique_ptr<Stopper> stopper)
+{
+    tg.set_stopper(stopper.release()->release());
+}
+
+void term_generator_set_stopper_strategy(TermGenerator &tg, int32_t strategy)
+{
+    tg.set_stopper_strategy(Xapian::TermGenerator::stop_strategy(strategy));
+}
+
 ////////////////////////////////////////////////////////////////
 
 std::unique_ptr<Document> new_document()
@@ -196,6 +402,101 @@ rust::String get_doc_data (Document &doc) {
    return doc.get_data();
 }
 
+void document_set_data_bytes(Document &doc, rust::Slice<const uint8_t> data)
+{
+    doc.set_data(from_rust_bytes(data));
+}
+
+rust::Vec<uint8_t> document_get_data_bytes(Document &doc)
+{
+    return to_rust_bytes(doc.get_data());
+}
+
+void document_add_value_bytes(Document &doc, valueno slot, rust::Slice<const uint8_t> value)
+{
+    doc.add_value(slot, from_rust_bytes(value));
+}
+
+rust::Vec<uint8_t> document_get_value_bytes(Document &doc, valueno slot)
+{
+    return to_rust_bytes(doc.get_value(slot));
+}
+
+docid document_get_docid(Document &doc)
+{
+    return doc.get_docid();
+}
+
+void document_add_term(Document &doc, rust::Str term, termcount wdf_inc)
+{
+    doc.add_term(std::string(term), wdf_inc);
+}
+
+void document_add_posting(Document &doc, rust::Str term, termpos pos, termcount wdf_inc)
+{
+    doc.add_posting(std::string(term), pos, wdf_inc);
+}
+
+void document_remove_term(Document &doc, rust::Str term)
+{
+    doc.remove_term(std::string(term));
+}
+
+void document_remove_posting(Document &doc, rust::Str term, termpos pos, termcount wdf_dec)
+{
+    doc.remove_posting(std::string(term), pos, wdf_dec);
+}
+
+void document_clear_terms(Document &doc)
+{
+    doc.clear_terms();
+}
+
+termcount document_termlist_count(Document &doc)
+{
+    return doc.termlist_count();
+}
+
+std::unique_ptr<TermIterator> document_termlist_begin(Document &doc)
+{
+    return std::make_unique<Xapian::TermIterator>(doc.termlist_begin());
+}
+
+std::unique_ptr<TermIterator> document_termlist_end(Document &doc)
+{
+    return std::make_unique<Xapian::TermIterator>(doc.termlist_end());
+}
+
+rust::String document_get_value(Document &doc, valueno slot)
+{
+    return doc.get_value(slot);
+}
+
+void document_remove_value(Document &doc, valueno slot)
+{
+    doc.remove_value(slot);
+}
+
+void document_clear_values(Document &doc)
+{
+    doc.clear_values();
+}
+
+valueno document_values_count(Document &doc)
+{
+    return doc.values_count();
+}
+
+std::unique_ptr<ValueIterator> document_values_begin(Document &doc)
+{
+    return std::make_unique<Xapian::ValueIterator>(doc.values_begin());
+}
+
+std::unique_ptr<ValueIterator> document_values_end(Document &doc)
+{
+    return std::make_unique<Xapian::ValueIterator>(doc.values_end());
+}
+
 //////
 
 std::unique_ptr<QueryParser> new_query_parser()
@@ -221,20 +522,36 @@ void add_prefix(QueryParser &qp, rust::Str field, rust::Str prefix)
     qp.add_prefix(std::string(field), std::string(prefix));
 }
 
-void add_rangeprocessor(QueryParser &qp, RangeProcessor &range_proc) {
+void add_boolean_prefix(QueryParser &qp, rust::Str field, rust::Str prefix)
+{
     std::string empty_grouping;
-    qp.add_rangeprocessor(&range_proc, &empty_grouping);
+    qp.add_boolean_prefix(std::string(field), std::string(prefix), &empty_grouping);
 }
 
-void add_number_rangeprocessor(QueryParser &qp, NumberRangeProcessor &range_proc) {
-    std::string empty_grouping;
-    qp.add_rangeprocessor(&range_proc, &empty_grouping);
+// calls back into a rust FieldProcessor for the text of each field
+class FieldProcessorTrampoline : public Xapian::FieldProcessor {
+    rust::Box<RustFieldProcessor> processor;
+
+  public:
+    FieldProcessorTrampoline(rust::Box<RustFieldProcessor> processor_) : processor(std::move(processor_)) {}
+
+    Xapian::Query operator()(const std::string &str) override {
+        return *field_processor_process(*processor, rust::Str(str));
+    }
+};
+
+std::unique_ptr<FieldProcessor> new_rust_field_processor(rust::Box<RustFieldProcessor> processor) {
+    return std::make_unique<FieldProcessorTrampoline>(std::move(processor));
 }
 
-void add_boolean_prefix(QueryParser &qp, rust::Str field, rust::Str prefix)
-{
+// the processor is released, so the query parser owns it from now on
+void query_parser_add_prefix_processor(QueryParser &qp, rust::Str field, std::unique_ptr<FieldProcessor> processor) {
+    qp.add_prefix(std::string(field), processor.release()->release());
+}
+
+void query_parser_add_boolean_prefix_processor(QueryParser &qp, rust::Str field, std::unique_ptr<FieldProcessor> processor) {
     std::string empty_grouping;
-    qp.add_boolean_prefix(std::string(field), std::string(prefix), &empty_grouping);
+    qp.add_boolean_prefix(std::string(field), processor.release()->release(), &empty_grouping);
 }
 
 std::unique_ptr<Query> parse_query(QueryParser &qp, rust::Str data, int32_t flags) {
@@ -245,6 +562,75 @@ std::unique_ptr<Query> parse_query_with_prefix(QueryParser &qp, rust::Str query,
     return std::make_unique<Xapian::Query>(qp.parse_query(std::string(query), flags, std::string(prefix)));
 }
 
+rust::String query_parser_get_corrected_query_string(QueryParser &qp) {
+    return qp.get_corrected_query_string();
+}
+
+// the stopper is released, so the query parser owns it from now on
+void query_parser_set_stopper(QueryParser &qp, std::unique_ptr<Stopper> stopper) {
+    qp.set_stopper(stopper.release()->release());
+}
+
+std::unique_ptr<TermIterator> query_parser_stoplist_begin(QueryParser &qp) {
+    return std::make_unique<Xapian::TermIterator>(qp.stoplist_begin());
+}
+
+std::unique_ptr<TermIterator> query_parser_stoplist_end(QueryParser &qp) {
+    return std::make_unique<Xapian::TermIterator>(qp.stoplist_end());
+}
+
+////////
+
+// SimpleStopper and StemStopper are handed around as their Stopper base class,
+// the add functions are only ever called with the subclass they were created as
+std::unique_ptr<Stopper> new_simple_stopper() {
+    return std::make_unique<Xapian::SimpleStopper>();
+}
+
+void simple_stopper_add(Stopper &stopper, rust::Str word) {
+    static_cast<Xapian::SimpleStopper &>(stopper).add(std::string(word));
+}
+
+std::unique_ptr<Stopper> new_stem_stopper(const Stem &stemmer, int32_t strategy) {
+#if XAPIAN_AT_LEAST(1, 5, 0)
+    return std::make_unique<Xapian::StemStopper>(stemmer, Xapian::StemStopper::stem_strategy(strategy));
+#else
+    (void)stemmer;
+    (void)strategy;
+    throw Xapian::FeatureUnavailableError("StemStopper requires xapian 1.5");
+#endif
+}
+
+void stem_stopper_add(Stopper &stopper, rust::Str word) {
+#if XAPIAN_AT_LEAST(1, 5, 0)
+    static_cast<Xapian::StemStopper &>(stopper).add(std::string(word));
+#else
+    (void)stopper;
+    (void)word;
+    throw Xapian::FeatureUnavailableError("StemStopper requires xapian 1.5");
+#endif
+}
+
+// calls back into a rust closure for each word
+class StopperTrampoline : public Xapian::Stopper {
+    rust::Box<RustStopper> stopper;
+
+  public:
+    StopperTrampoline(rust::Box<RustStopper> stopper_) : stopper(std::move(stopper_)) {}
+
+    bool operator()(const std::string &word) const override {
+        return stopper_is_stop_word(*stopper, rust::Str(word));
+    }
+};
+
+std::unique_ptr<Stopper> new_rust_stopper(rust::Box<RustStopper> stopper) {
+    return std::make_unique<StopperTrampoline>(std::move(stopper));
+}
+
+bool stopper_is_stop(const Stopper &stopper, rust::Str word) {
+    return stopper(std::string(word));
+}
+
 ////////
 
 std::unique_ptr<Query> new_query() {
@@ -259,7 +645,7 @@ std::unique_ptr<Query> new_query_range(int32_t _op, valueno slot, double _begin,
     return std::make_unique<Xapian::Query>(_query);
 }
 
-std::unique_ptr<Query> add_right_query(Query &this_q, int32_t _op, Query &q) {
+std::unique_ptr<Query> add_right_query(const Query &this_q, int32_t _op, const Query &q) {
     return std::make_unique<Xapian::Query>((Xapian::Query::op)_op, this_q, q);
 }
 
@@ -270,6 +656,183 @@ std::unique_ptr<Query> new_query_double_with_prefix(rust::Str prefix, double _d)
     return std::make_unique<Xapian::Query>(_query);
 }
 
+std::unique_ptr<Query> new_query_term(rust::Str term, termcount wqf, termpos pos) {
+    return std::make_unique<Xapian::Query>(std::string(term), wqf, pos);
+}
+
+std::unique_ptr<Query> new_query_match_all() {
+    return std::make_unique<Xapian::Query>(Xapian::Query::MatchAll);
+}
+
+std::unique_ptr<Query> new_query_scale_weight(double factor, const Query &subquery) {
+    return std::make_unique<Xapian::Query>(factor, subquery);
+}
+
+std::unique_ptr<Query> new_query_wildcard(rust::Str pattern, termcount max_expansion, int32_t flags, int32_t combiner) {
+    return std::make_unique<Xapian::Query>(Xapian::Query::OP_WILDCARD, std::string(pattern), max_expansion, flags, (Xapian::Query::op)combiner);
+}
+
+std::unique_ptr<Query> new_query_edit_distance(rust::Str pattern, uint32_t edit_distance, termcount max_expansion, int32_t flags, int32_t combiner, size_t min_prefix_len) {
+#if XAPIAN_AT_LEAST(1, 5, 0)
+    return std::make_unique<Xapian::Query>(Xapian::Query::OP_EDIT_DISTANCE, std::string(pattern), max_expansion, flags, (Xapian::Query::op)combiner, edit_distance, min_prefix_len);
+#else
+    (void)pattern;
+    (void)edit_distance;
+    (void)max_expansion;
+    (void)flags;
+    (void)combiner;
+    (void)min_prefix_len;
+    throw Xapian::FeatureUnavailableError("OP_EDIT_DISTANCE requires xapian 1.5");
+#endif
+}
+
+// calls back into a rust PostingSource, owned by the query once released
+class PostingSourceTrampoline : public Xapian::PostingSource {
+    rust::Box<RustPostingSource> source;
+
+    // only tell the matcher about a new maxweight when it changes, as that is not cheap
+    void update_maxweight() {
+        double max_weight = posting_source_get_maxweight(*source);
+        if (max_weight != get_maxweight()) {
+            set_maxweight(max_weight);
+        }
+    }
+
+  public:
+    PostingSourceTrampoline(rust::Box<RustPostingSource> source_) : source(std::move(source_)) {}
+
+    Xapian::doccount get_termfreq_min() const override {
+        return posting_source_get_termfreq_min(*source);
+    }
+
+    Xapian::doccount get_termfreq_est() const override {
+        return posting_source_get_termfreq_est(*source);
+    }
+
+    Xapian::doccount get_termfreq_max() const override {
+        return posting_source_get_termfreq_max(*source);
+    }
+
+    double get_weight() const override {
+        return posting_source_get_weight(*source);
+    }
+
+    Xapian::docid get_docid() const override {
+        return posting_source_get_docid(*source);
+    }
+
+    void next(double min_wt) override {
+        posting_source_next(*source, min_wt);
+        update_maxweight();
+    }
+
+    void skip_to(Xapian::docid did, double min_wt) override {
+        posting_source_skip_to(*source, did, min_wt);
+        update_maxweight();
+    }
+
+    bool check(Xapian::docid did, double min_wt) override {
+        bool result = posting_source_check(*source, did, min_wt);
+        update_maxweight();
+        return result;
+    }
+
+    bool at_end() const override {
+        return posting_source_at_end(*source);
+    }
+
+    Xapian::PostingSource *clone() const override {
+        return new PostingSourceTrampoline(posting_source_clone(*source));
+    }
+
+    void init(const Xapian::Database &db) override {
+        posting_source_init(*source, std::make_unique<Xapian::Database>(db));
+        update_maxweight();
+    }
+};
+
+std::unique_ptr<Query> new_query_from_posting_source(rust::Box<RustPostingSource> source) {
+    return std::make_unique<Xapian::Query>((new PostingSourceTrampoline(std::move(source)))->release());
+}
+
+std::unique_ptr<Query> new_query_from_builtin_posting_source(std::unique_ptr<PostingSource> source) {
+    return std::make_unique<Xapian::Query>(source.release()->release());
+}
+
+std::unique_ptr<Query> new_query_from_value_map_posting_source(std::unique_ptr<ValueMapPostingSource> source) {
+    return std::make_unique<Xapian::Query>(source.release()->release());
+}
+
+std::unique_ptr<Query> new_query_from_vector(int32_t op, const QueryVector &subqueries, termcount parameter) {
+    return std::make_unique<Xapian::Query>((Xapian::Query::op)op, subqueries.begin(), subqueries.end(), parameter);
+}
+
+std::unique_ptr<QueryVector> new_query_vector() {
+    return std::make_unique<QueryVector>();
+}
+
+void query_vector_push(QueryVector &subqueries, const Query &q) {
+    subqueries.push_back(q);
+}
+
+rust::Vec<uint8_t> query_serialise(const Query &q) {
+    return to_rust_bytes(q.serialise());
+}
+
+std::unique_ptr<Query> query_unserialise(rust::Slice<const uint8_t> serialised, const Registry &registry) {
+    return std::make_unique<Xapian::Query>(Xapian::Query::unserialise(from_rust_bytes(serialised), registry));
+}
+
+int32_t query_get_type(const Query &q) {
+    return q.get_type();
+}
+
+size_t query_get_num_subqueries(const Query &q) {
+    return q.get_num_subqueries();
+}
+
+std::unique_ptr<Query> query_get_subquery(const Query &q, size_t n) {
+    return std::make_unique<Xapian::Query>(q.get_subquery(n));
+}
+
+termcount query_get_leaf_wqf(const Query &q) {
+#if XAPIAN_AT_LEAST(1, 5, 0)
+    return q.get_leaf_wqf();
+#else
+    (void)q;
+    throw Xapian::FeatureUnavailableError("Query::get_leaf_wqf() requires xapian 1.5");
+#endif
+}
+
+termpos query_get_leaf_pos(const Query &q) {
+#if XAPIAN_AT_LEAST(1, 5, 0)
+    return q.get_leaf_pos();
+#else
+    (void)q;
+    throw Xapian::FeatureUnavailableError("Query::get_leaf_pos() requires xapian 1.5");
+#endif
+}
+
+termcount query_get_length(const Query &q) {
+    return q.get_length();
+}
+
+std::unique_ptr<TermIterator> query_get_terms_begin(const Query &q) {
+    return std::make_unique<Xapian::TermIterator>(q.get_terms_begin());
+}
+
+std::unique_ptr<TermIterator> query_get_terms_end(const Query &q) {
+    return std::make_unique<Xapian::TermIterator>(q.get_terms_end());
+}
+
+std::unique_ptr<TermIterator> query_get_unique_terms_begin(const Query &q) {
+    return std::make_unique<Xapian::TermIterator>(q.get_unique_terms_begin());
+}
+
+std::unique_ptr<TermIterator> query_get_unique_terms_end(const Query &q) {
+    return std::make_unique<Xapian::TermIterator>(q.get_unique_terms_end());
+}
+
 bool query_is_empty (Query &q) {
     return q.empty();
 }
@@ -292,18 +855,108 @@ void set_sort_by_key(Enquire &en, MultiValueKeyMaker &sorter, bool reverse) {
     en.set_sort_by_key(&sorter, reverse);
 }
 
+void enquire_set_sort_by_lat_long_distance_key(Enquire &en, LatLongDistanceKeyMaker &sorter, bool reverse) {
+    en.set_sort_by_key(&sorter, reverse);
+}
+
 void add_matchspy_value_count(Enquire &en, ValueCountMatchSpy &vcms) {
     en.add_matchspy(&vcms);
 }
 
-void enquire_set_weighting_scheme_bool(Enquire &en, BoolWeight &weight) {
-    en.set_weighting_scheme(weight);
+// calls back into a rust MatchSpy for each candidate document
+class MatchSpyTrampoline : public Xapian::MatchSpy {
+    rust::Box<RustMatchSpy> spy;
+
+  public:
+    MatchSpyTrampoline(rust::Box<RustMatchSpy> spy_) : spy(std::move(spy_)) {}
+
+    void operator()(const Xapian::Document &doc, double wt) override {
+        match_spy_observe(*spy, std::make_unique<Xapian::Document>(doc), wt);
+    }
+
+    std::string name() const override {
+        return "RustMatchSpy";
+    }
+};
+
+std::unique_ptr<MatchSpy> new_rust_match_spy(rust::Box<RustMatchSpy> spy) {
+    return std::make_unique<MatchSpyTrampoline>(std::move(spy));
+}
+
+void enquire_add_matchspy(Enquire &en, MatchSpy &spy) {
+    en.add_matchspy(&spy);
 }
 
-void enquire_set_weighting_scheme_bm25(Enquire &en, BM25Weight &weight) {
+void enquire_set_weighting_scheme(Enquire &en, const Weight &weight) {
     en.set_weighting_scheme(weight);
 }
 
+// calls back into a rust Weight, xapian clones the scheme for each term in the query
+class WeightTrampoline : public Xapian::Weight {
+    rust::Box<RustWeight> weight;
+    // xapian's own copy of the flags is private
+    int stats_wanted;
+
+  public:
+    WeightTrampoline(rust::Box<RustWeight> weight_)
+        : weight(std::move(weight_)), stats_wanted(weight_stats_needed(*weight)) {
+        need_stat(stat_flags(stats_wanted));
+    }
+
+    Xapian::Weight *clone() const override {
+        return new WeightTrampoline(weight_clone(*weight));
+    }
+
+    // only the statistics asked for in the constructor are valid, the rest are left zeroed
+    void init(double factor) override {
+        WeightStats stats = {};
+        if (stats_wanted & COLLECTION_SIZE) stats.collection_size = get_collection_size();
+        if (stats_wanted & RSET_SIZE) stats.rset_size = get_rset_size();
+        if (stats_wanted & AVERAGE_LENGTH) stats.average_length = get_average_length();
+        if (stats_wanted & TERMFREQ) stats.termfreq = get_termfreq();
+        if (stats_wanted & RELTERMFREQ) stats.reltermfreq = get_reltermfreq();
+        if (stats_wanted & COLLECTION_FREQ) stats.collection_freq = get_collection_freq();
+        if (stats_wanted & QUERY_LENGTH) stats.query_length = get_query_length();
+        if (stats_wanted & WQF) stats.wqf = get_wqf();
+        if (stats_wanted & DOC_LENGTH_MIN) stats.doclength_lower_bound = get_doclength_lower_bound();
+        if (stats_wanted & DOC_LENGTH_MAX) stats.doclength_upper_bound = get_doclength_upper_bound();
+        if (stats_wanted & WDF_MAX) stats.wdf_upper_bound = get_wdf_upper_bound();
+        if ((stats_wanted & TOTAL_LENGTH) == TOTAL_LENGTH) stats.total_length = get_total_length();
+        weight_init(*weight, factor, stats);
+    }
+
+#if XAPIAN_AT_LEAST(1, 5, 0)
+    double get_sumpart(termcount wdf, termcount doclen, termcount uniqterms, termcount) const override {
+        return weight_get_sumpart(*weight, wdf, doclen, uniqterms);
+    }
+
+    double get_sumextra(termcount doclen, termcount uniqterms, termcount) const override {
+        return weight_get_sumextra(*weight, doclen, uniqterms);
+    }
+#else
+    double get_sumpart(termcount wdf, termcount doclen, termcount uniqterms) const override {
+        return weight_get_sumpart(*weight, wdf, doclen, uniqterms);
+    }
+
+    double get_sumextra(termcount doclen, termcount uniqterms) const override {
+        return weight_get_sumextra(*weight, doclen, uniqterms);
+    }
+#endif
+
+    double get_maxpart() const override {
+        return weight_get_maxpart(*weight);
+    }
+
+    double get_maxextra() const override {
+        return weight_get_maxextra(*weight);
+    }
+};
+
+void enquire_set_weighting_scheme_custom(Enquire &en, rust::Box<RustWeight> weight) {
+    WeightTrampoline trampoline(std::move(weight));
+    en.set_weighting_scheme(trampoline);
+}
+
 void enquire_set_docid_order(Enquire &en, int32_t order) {
     en.set_docid_order(Enquire::docid_order(order));
 }
@@ -319,6 +972,130 @@ void enquire_set_sort_by_value(Enquire &en, valueno sort_key, bool reverse) {
 void enquire_set_collapse_key(Enquire &en, valueno collapse_key, doccount collapse_max = 1) {
     en.set_collapse_key(collapse_key, collapse_max);
 }
+
+std::unique_ptr<MSet> enquire_get_mset_with_rset(Enquire &en, doccount from, doccount size, RSet &rset) {
+    return std::make_unique<Xapian::MSet>(en.get_mset(from, size, &rset));
+}
+
+// calls back into a rust closure for each candidate document
+class MatchDeciderTrampoline : public Xapian::MatchDecider {
+    const RustMatchDecider &decider;
+
+  public:
+    MatchDeciderTrampoline(const RustMatchDecider &decider_) : decider(decider_) {}
+
+    bool operator()(const Xapian::Document &doc) const override {
+        return match_decider_accept(decider, std::make_unique<Xapian::Document>(doc));
+    }
+};
+
+std::unique_ptr<MSet> enquire_get_mset_with_decider(Enquire &en, doccount from, doccount size, doccount checkatleast, const RustMatchDecider &decider) {
+    MatchDeciderTrampoline trampoline(decider);
+    return std::make_unique<Xapian::MSet>(en.get_mset(from, size, checkatleast, NULL, &trampoline));
+}
+
+std::unique_ptr<ESet> enquire_get_eset(Enquire &en, termcount maxitems, RSet &rset, int32_t flags, double min_wt) {
+    return std::make_unique<Xapian::ESet>(en.get_eset(maxitems, rset, flags, NULL, min_wt));
+}
+
+// calls back into a rust closure for each candidate expand term
+class ExpandDeciderTrampoline : public Xapian::ExpandDecider {
+    const RustExpandDecider &decider;
+
+  public:
+    ExpandDeciderTrampoline(const RustExpandDecider &decider_) : decider(decider_) {}
+
+    bool operator()(const std::string &term) const override {
+        // passed as bytes, rust::Str would throw on terms which aren't valid UTF-8
+        return expand_decider_accept(decider, rust::Slice<const uint8_t>(reinterpret_cast<const uint8_t *>(term.data()), term.size()));
+    }
+};
+
+std::unique_ptr<ESet> enquire_get_eset_with_decider(Enquire &en, termcount maxitems, RSet &rset, int32_t flags, const RustExpandDecider &decider, double min_wt) {
+    ExpandDeciderTrampoline trampoline(decider);
+    return std::make_unique<Xapian::ESet>(en.get_eset(maxitems, rset, flags, &trampoline, min_wt));
+}
+
+/////
+
+std::unique_ptr<RSet> new_rset() {
+    return std::make_unique<Xapian::RSet>();
+}
+
+void rset_add_document(RSet &rset, docid did) {
+    rset.add_document(did);
+}
+
+void rset_remove_document(RSet &rset, docid did) {
+    rset.remove_document(did);
+}
+
+bool rset_contains(RSet &rset, docid did) {
+    return rset.contains(did);
+}
+
+doccount rset_size(RSet &rset) {
+    return rset.size();
+}
+
+bool rset_empty(RSet &rset) {
+    return rset.empty();
+}
+
+/////
+
+termcount eset_size(ESet &set) {
+    return set.size();
+}
+
+termcount eset_get_ebound(ESet &set) {
+    return set.get_ebound();
+}
+
+std::unique_ptr<ESet> eset_clone(const ESet &set) {
+    return std::make_unique<Xapian::ESet>(set);
+}
+
+std::unique_ptr<ESetIterator> eset_begin(ESet &set) {
+    return std::make_unique<Xapian::ESetIterator>(set.begin());
+}
+
+std::unique_ptr<ESetIterator> eset_end(ESet &set) {
+    return std::make_unique<Xapian::ESetIterator>(set.end());
+}
+
+std::unique_ptr<ESetIterator> eset_iterator_at(ESet &set, termcount index) {
+    if (index >= set.size()) {
+        throw Xapian::RangeError("ESet index out of range");
+    }
+    return std::make_unique<Xapian::ESetIterator>(set[index]);
+}
+
+// like MSetIterator, an ESetIterator doesn't check its position when dereferenced
+static void check_eset_iterator(const ESetIterator &iter) {
+    if (iter.off_from_end == 0 || iter.off_from_end > iter.eset.size()) {
+        throw Xapian::RangeError("ESetIterator is not positioned on an item of the ESet");
+    }
+}
+
+rust::String eset_iterator_get_term(ESetIterator &iter) {
+    check_eset_iterator(iter);
+    return *iter;
+}
+
+double eset_iterator_get_weight(ESetIterator &iter) {
+    check_eset_iterator(iter);
+    return iter.get_weight();
+}
+
+bool eset_iterator_eq(ESetIterator &iter, ESetIterator &other) {
+    return iter == other;
+}
+
+void eset_iterator_next(ESetIterator &iter) {
+    check_eset_iterator(iter);
+    ++iter;
+}
 /////
 
 int get_matches_estimated (MSet &set) {
@@ -341,11 +1118,31 @@ std::unique_ptr<MSetIterator> mset_back (MSet &set) {
     return std::make_unique<Xapian::MSetIterator>(set.back());
 }
 
+std::unique_ptr<MSetIterator> mset_iterator_at (MSet &set, doccount index) {
+    if (index >= set.size()) {
+        throw Xapian::RangeError("MSet index out of range");
+    }
+    return std::make_unique<Xapian::MSetIterator>(set[index]);
+}
+
+std::unique_ptr<MSet> mset_clone (const MSet &set) {
+    return std::make_unique<Xapian::MSet>(set);
+}
+
 rust::String mset_snippet(MSet &set, rust::Str text, int32_t length, Stem &stem, int32_t flags, rust::Str hi_start,rust::Str hi_end, rust::Str omit) {
     return set.snippet(std::string(text), length, stem, flags, std::string(hi_start), std::string(hi_end), std::string(omit));;
 }
 
+// Xapian doesn't check the position when dereferencing an MSetIterator,
+// off_from_end is 0 at end() and greater than the size before begin()
+static void check_mset_iterator(const MSetIterator &iter) {
+    if (iter.off_from_end == 0 || iter.off_from_end > iter.mset.size()) {
+        throw Xapian::RangeError("MSetIterator is not positioned on an item of the MSet");
+    }
+}
+
 std::unique_ptr<Document> mset_iterator_get_document(MSetIterator &iter) {
+    check_mset_iterator(iter);
     return std::make_unique<Xapian::Document>(iter.get_document());
 }
 
@@ -354,9 +1151,40 @@ bool mset_iterator_eq(MSetIterator &iter, MSetIterator &other) {
 }
 
 void mset_iterator_next (MSetIterator &iter) {
+    check_mset_iterator(iter);
     iter++;
 }
 
+docid mset_iterator_get_docid(MSetIterator &iter) {
+    check_mset_iterator(iter);
+    return *iter;
+}
+
+doccount mset_iterator_get_rank(MSetIterator &iter) {
+    check_mset_iterator(iter);
+    return iter.get_rank();
+}
+
+double mset_iterator_get_weight(MSetIterator &iter) {
+    check_mset_iterator(iter);
+    return iter.get_weight();
+}
+
+int32_t mset_iterator_get_percent(MSetIterator &iter) {
+    check_mset_iterator(iter);
+    return iter.get_percent();
+}
+
+doccount mset_iterator_get_collapse_count(MSetIterator &iter) {
+    check_mset_iterator(iter);
+    return iter.get_collapse_count();
+}
+
+rust::Vec<uint8_t> mset_iterator_get_collapse_key(MSetIterator &iter) {
+    check_mset_iterator(iter);
+    return to_rust_bytes(iter.get_collapse_key());
+}
+
 /////
 
 std::unique_ptr<MultiValueKeyMaker> new_multi_value_key_maker () {
@@ -373,33 +1201,90 @@ std::unique_ptr<ValueCountMatchSpy> new_value_count_match_spy (valueno slot) {
     return std::make_unique<Xapian::ValueCountMatchSpy>(slot);
 }
 
+std::unique_ptr<PostingSource> new_value_weight_posting_source(valueno slot) {
+    return std::make_unique<Xapian::ValueWeightPostingSource>(slot);
+}
+
+std::unique_ptr<PostingSource> new_decreasing_value_weight_posting_source(valueno slot, docid range_start, docid range_end) {
+    return std::make_unique<Xapian::DecreasingValueWeightPostingSource>(slot, range_start, range_end);
+}
+
+std::unique_ptr<PostingSource> new_fixed_weight_posting_source(double wt) {
+    return std::make_unique<Xapian::FixedWeightPostingSource>(wt);
+}
+
+std::unique_ptr<ValueMapPostingSource> new_value_map_posting_source(valueno slot) {
+    return std::make_unique<Xapian::ValueMapPostingSource>(slot);
+}
+
+std::unique_ptr<PostingSource> new_lat_long_distance_posting_source(valueno slot, const LatLongCoords &centre, const GreatCircleMetric &metric, double max_range, double k1, double k2) {
+    return std::make_unique<Xapian::LatLongDistancePostingSource>(slot, centre, metric, max_range, k1, k2);
+}
+
+void value_map_posting_source_add_mapping(ValueMapPostingSource &source, rust::Str key, double wt) {
+    source.add_mapping(std::string(key), wt);
+}
+
+void value_map_posting_source_clear_mappings(ValueMapPostingSource &source) {
+    source.clear_mappings();
+}
+
+void value_map_posting_source_set_default_weight(ValueMapPostingSource &source, double wt) {
+    source.set_default_weight(wt);
+}
+
 /////
 
-std::unique_ptr<RangeProcessor> new_range_processor (valueno slot, rust::Str str, int32_t flags) {
-    // https://xapian.org/docs/sourcedoc/html/classXapian_1_1RangeProcessor.html#aca78f2633f761f70a2e94e62e741f0ba
-    //        Xapian::RangeProcessor::RangeProcessor (	Xapian::valueno 	slot_,const std::string & 	str_ = std::string(),unsigned 	flags_ = 0 )
-    //        slot_	Which value slot to generate ranges over.
-    //                str_	A string to look for to recognise values as belonging to this range (as a prefix by default, or as a suffix if flags Xapian::RP_SUFFIX is specified).
-    //        flags_	Zero or more of the following flags, combined with bitwise-or (| in C++):
-    //        Xapian::RP_SUFFIX - require str_ as a suffix instead of a prefix.
-    //        Xapian::RP_REPEATED - optionally allow str_ on both ends of the range - e.g. $1..$10 or 5m..50m.
-    //                By default a prefix is only checked for on the start (e.g. date:1/1/1980..31/12/1989), and a suffix only on the end (e.g. 2..12kg).
-
-    // enum {
-    //    RP_SUFFIX = 1,
-    //    RP_REPEATED = 2,
-    //    RP_DATE_PREFER_MDY = 4
-    //};
-    // when flags = 0, str as a prefix by default
-    return std::make_unique<Xapian::RangeProcessor>(slot, std::string(str), flags);
+// calls back into a rust RangeProcessor, the prefix or suffix is checked and removed by
+// RangeProcessor::check_range before operator() is called
+class RangeProcessorTrampoline : public Xapian::RangeProcessor {
+    rust::Box<RustRangeProcessor> processor;
+
+  public:
+    RangeProcessorTrampoline(const std::string &str_, unsigned flags_, rust::Box<RustRangeProcessor> processor_)
+        : Xapian::RangeProcessor(Xapian::BAD_VALUENO, str_, flags_), processor(std::move(processor_)) {}
+
+    Xapian::Query operator()(const std::string &begin, const std::string &end) override {
+        std::unique_ptr<Query> query = range_processor_check_range(*processor, rust::Str(begin), rust::Str(end));
+        if (!query) {
+            return Xapian::Query(Xapian::Query::OP_INVALID);
+        }
+        return *query;
+    }
+};
+
+std::unique_ptr<RangeProcessor> new_rust_range_processor(rust::Str prefix, int32_t flags, rust::Box<RustRangeProcessor> processor) {
+    return std::make_unique<RangeProcessorTrampoline>(std::string(prefix), flags, std::move(processor));
+}
+
+// the range processor is released, so the query parser owns it from now on
+void query_parser_add_rangeprocessor(QueryParser &qp, std::unique_ptr<RangeProcessor> range_proc, bool has_grouping, rust::Str grouping) {
+    std::string grouping_str(grouping);
+    qp.add_rangeprocessor(range_proc.release()->release(), has_grouping ? &grouping_str : NULL);
 }
 
 /////
 
-std::unique_ptr<NumberRangeProcessor> new_number_range_processor (valueno slot, rust::Str prefix, int32_t flags) {
+// NumberRangeProcessor, DateRangeProcessor and UnitRangeProcessor add no methods of their own,
+// so they are handed around as their RangeProcessor base class
+std::unique_ptr<RangeProcessor> new_number_range_processor (valueno slot, rust::Str prefix, int32_t flags) {
     return std::make_unique<Xapian::NumberRangeProcessor>(slot, std::string(prefix), flags);
 }
 
+std::unique_ptr<RangeProcessor> new_date_range_processor (valueno slot, rust::Str prefix, int32_t flags, int32_t epoch_year) {
+    return std::make_unique<Xapian::DateRangeProcessor>(slot, std::string(prefix), flags, epoch_year);
+}
+
+std::unique_ptr<RangeProcessor> new_unit_range_processor (valueno slot, rust::Str prefix) {
+#if XAPIAN_AT_LEAST(1, 5, 0)
+    return std::make_unique<Xapian::UnitRangeProcessor>(slot, std::string(prefix));
+#else
+    (void)slot;
+    (void)prefix;
+    throw Xapian::FeatureUnavailableError("UnitRangeProcessor requires xapian 1.5");
+#endif
+}
+
 /////
 int value_count_matchspy_get_total(ValueCountMatchSpy &vcms) {
     return vcms.get_total();
@@ -413,11 +1298,27 @@ std::unique_ptr<TermIterator> value_count_matchspy_values_end(ValueCountMatchSpy
     return std::make_unique<Xapian::TermIterator>(vcms.values_end());
 }
 
+// TermIterator and ValueIterator don't check their position when dereferenced or advanced,
+// at the end their internal is null
+static void check_term_iterator(const TermIterator &titer) {
+    if (!titer.internal) {
+        throw Xapian::RangeError("TermIterator is at the end");
+    }
+}
+
+static void check_value_iterator(const ValueIterator &viter) {
+    if (!viter.internal) {
+        throw Xapian::RangeError("ValueIterator is at the end");
+    }
+}
+
 rust::String term_iterator_get_termfreq_value(TermIterator &titer) {
-   return rust::String(*titer);
+    check_term_iterator(titer);
+    return rust::String(*titer);
 }
 
 int term_iterator_get_termfreq_freq(TermIterator &titer) {
+    check_term_iterator(titer);
     return titer.get_termfreq();
 }
 
@@ -425,18 +1326,166 @@ bool term_iterator_eq(TermIterator &titer, TermIterator &other) {
     return titer == other;
 }
 
+// the end iterator of metadata keys, spellings and synonyms is a default constructed TermIterator
+std::unique_ptr<TermIterator> new_term_iterator_end() {
+    return std::make_unique<Xapian::TermIterator>();
+}
+
+rust::String term_iterator_get_term(TermIterator &titer) {
+    check_term_iterator(titer);
+    return *titer;
+}
+
+termcount term_iterator_get_wdf(TermIterator &titer) {
+    check_term_iterator(titer);
+    return titer.get_wdf();
+}
+
 void term_iterator_next(TermIterator &titer) {
+    check_term_iterator(titer);
     ++titer;
 }
 
+/////
+
+rust::Vec<uint8_t> value_iterator_get_value(ValueIterator &viter) {
+    check_value_iterator(viter);
+    return to_rust_bytes(*viter);
+}
+
+valueno value_iterator_get_valueno(ValueIterator &viter) {
+    check_value_iterator(viter);
+    return viter.get_valueno();
+}
+
+bool value_iterator_eq(ValueIterator &viter, ValueIterator &other) {
+    return viter == other;
+}
+
+void value_iterator_next(ValueIterator &viter) {
+    check_value_iterator(viter);
+    ++viter;
+}
+
+////
+
+std::unique_ptr<Registry> new_registry() {
+    return std::make_unique<Xapian::Registry>();
+}
+
+//// Geospatial
+
+std::unique_ptr<LatLongCoords> new_lat_long_coords() {
+    return std::make_unique<Xapian::LatLongCoords>();
+}
+
+void lat_long_coords_append(LatLongCoords &coords, double latitude, double longitude) {
+    coords.append(Xapian::LatLongCoord(latitude, longitude));
+}
+
+size_t lat_long_coords_size(const LatLongCoords &coords) {
+    return coords.size();
+}
+
+// latitude and longitude of each coordinate in turn
+rust::Vec<double> lat_long_coords_flatten(const LatLongCoords &coords) {
+    rust::Vec<double> flat;
+    for (auto it = coords.begin(); it != coords.end(); ++it) {
+        flat.push_back((*it).latitude);
+        flat.push_back((*it).longitude);
+    }
+    return flat;
+}
+
+rust::Vec<uint8_t> lat_long_coords_serialise(const LatLongCoords &coords) {
+    return to_rust_bytes(coords.serialise());
+}
+
+void lat_long_coords_unserialise(LatLongCoords &coords, rust::Slice<const uint8_t> data) {
+    coords.unserialise(from_rust_bytes(data));
+}
+
+std::unique_ptr<GreatCircleMetric> new_great_circle_metric() {
+    return std::make_unique<Xapian::GreatCircleMetric>();
+}
+
+std::unique_ptr<GreatCircleMetric> new_great_circle_metric_with_radius(double radius) {
+    return std::make_unique<Xapian::GreatCircleMetric>(radius);
+}
+
+double great_circle_metric_distance(const GreatCircleMetric &metric, const LatLongCoords &a, const LatLongCoords &b) {
+    return metric(a, b);
+}
+
+std::unique_ptr<LatLongDistanceKeyMaker> new_lat_long_distance_key_maker(valueno slot, const LatLongCoords &centre, const GreatCircleMetric &metric, bool has_defdistance, double defdistance) {
+    if (has_defdistance) {
+        return std::make_unique<Xapian::LatLongDistanceKeyMaker>(slot, centre, metric, defdistance);
+    }
+    return std::make_unique<Xapian::LatLongDistanceKeyMaker>(slot, centre, metric);
+}
+
 //// Weight
 
-// BoolWeight
-std::unique_ptr<BoolWeight> new_bool_weight() {
+std::unique_ptr<Weight> new_bool_weight() {
     return std::make_unique<Xapian::BoolWeight>();
 }
 
-// BM25
-std::unique_ptr<BM25Weight> new_bm25_weight(double k1, double k2, double k3, double b, double min_normlen) {
+std::unique_ptr<Weight> new_tfidf_weight(rust::Str normalizations) {
+    return std::make_unique<Xapian::TfIdfWeight>(std::string(normalizations));
+}
+
+std::unique_ptr<Weight> new_bm25_weight(double k1, double k2, double k3, double b, double min_normlen) {
     return std::make_unique<Xapian::BM25Weight>(k1, k2, k3, b, min_normlen);
+}
+
+std::unique_ptr<Weight> new_bm25plus_weight(double k1, double k2, double k3, double b, double min_normlen, double delta) {
+    return std::make_unique<Xapian::BM25PlusWeight>(k1, k2, k3, b, min_normlen, delta);
+}
+
+std::unique_ptr<Weight> new_inl2_weight(double c) {
+    return std::make_unique<Xapian::InL2Weight>(c);
+}
+
+std::unique_ptr<Weight> new_ifb2_weight(double c) {
+    return std::make_unique<Xapian::IfB2Weight>(c);
+}
+
+std::unique_ptr<Weight> new_ineb2_weight(double c) {
+    return std::make_unique<Xapian::IneB2Weight>(c);
+}
+
+std::unique_ptr<Weight> new_bb2_weight(double c) {
+    return std::make_unique<Xapian::BB2Weight>(c);
+}
+
+std::unique_ptr<Weight> new_dlh_weight() {
+    return std::make_unique<Xapian::DLHWeight>();
+}
+
+std::unique_ptr<Weight> new_pl2_weight(double c) {
+    return std::make_unique<Xapian::PL2Weight>(c);
+}
+
+std::unique_ptr<Weight> new_pl2plus_weight(double c, double delta) {
+    return std::make_unique<Xapian::PL2PlusWeight>(c, delta);
+}
+
+std::unique_ptr<Weight> new_dph_weight() {
+    return std::make_unique<Xapian::DPHWeight>();
+}
+
+std::unique_ptr<Weight> new_lm_weight(double param_log, int32_t smoothing, double smoothing1, double smoothing2) {
+    return std::make_unique<Xapian::LMWeight>(param_log, Xapian::Weight::type_smoothing(smoothing), smoothing1, smoothing2);
+}
+
+std::unique_ptr<Weight> new_coord_weight() {
+    return std::make_unique<Xapian::CoordWeight>();
+}
+
+std::unique_ptr<Weight> new_dice_coeff_weight() {
+#if XAPIAN_AT_LEAST(1, 5, 0)
+    return std::make_unique<Xapian::DiceCoeffWeight>();
+#else
+    throw Xapian::FeatureUnavailableError("DiceCoeffWeight requires xapian 1.5");
+#endif
 }
\ No newline at end of file
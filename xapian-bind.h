@@ -3,6 +3,7 @@
 #include <memory>
 #include <xapian.h>
 #include <string>
+#include <vector>
 #include <string.h>
 
 #include <stdexcept>
@@ -12,11 +13,36 @@
 // https://github.com/dtolnay/cxx/pull/74/files#diff-b43c1d065c83e99920c09c2d8dbed19687b44a3aeb8e1400a6f5228064a3629f
 // https://cxx.rs/binding/result.html
 namespace rust::behavior {
+    // Xapian keeps the errno of an error private, so recover it from the
+    // error string which was formatted from it, 0 if it isn't a strerror() message
+    static int errno_from_error_string(const char *error_string) {
+        for (int n = 1; n < 256; ++n) {
+            if (strcmp(strerror(n), error_string) == 0) {
+                return n;
+            }
+        }
+        return 0;
+    }
+
     template <typename Try, typename Fail>
     static void trycatch(Try &&func, Fail &&fail) noexcept try {
         func();
     } catch (const Xapian::Error& e) {
-        fail("[Xapian Error] " + std::string(e.get_type()) + ": " + e.get_msg());
+        // type, msg, context, errno and error string are NUL separated, so the rust side
+        // can rebuild a typed xapian::Error from cxx::Exception::what()
+        std::string what(e.get_type());
+        what += '\0';
+        what += e.get_msg();
+        what += '\0';
+        what += e.get_context();
+        what += '\0';
+        const char *error_string = e.get_error_string();
+        what += std::to_string(error_string ? errno_from_error_string(error_string) : 0);
+        what += '\0';
+        if (error_string) {
+            what += error_string;
+        }
+        fail(what);
     } catch (const std::exception &e) {
         fail(e.what());
     }
@@ -24,6 +50,21 @@ namespace rust::behavior {
 
 using namespace Xapian;
 
+// opaque types implemented in rust, see the extern "Rust" block in src/lib.rs. Their callbacks
+// catch panics and return errors, which cxx throws here as rust::Error, so the trampolines
+// calling them let it propagate out through xapian to the caller
+struct RustExpandDecider;
+struct RustMatchSpy;
+struct RustMatchDecider;
+struct RustWeight;
+struct RustPostingSource;
+struct RustStopper;
+struct RustFieldProcessor;
+struct RustRangeProcessor;
+
+// list of subqueries used to build n-ary queries
+using QueryVector = std::vector<Xapian::Query>;
+
 rust::Str version_string();
 
 std::unique_ptr<Database> new_database();
@@ -34,6 +75,25 @@ std::unique_ptr<Database> new_database_with_path(rust::Str path, int32_t action)
 void database_reopen (Database &db);
 void add_database(Database &db, Database &add_db);
 void database_close(Database &db);
+std::unique_ptr<Document> database_get_document(Database &db, docid did);
+doccount database_get_doccount(Database &db);
+docid database_get_lastdocid(Database &db);
+double database_get_avlength(Database &db);
+doccount database_get_termfreq(Database &db, rust::Str term);
+termcount database_get_collection_freq(Database &db, rust::Str term);
+bool database_term_exists(Database &db, rust::Str term);
+doccount database_get_value_freq(Database &db, valueno slot);
+rust::Vec<uint8_t> database_get_value_lower_bound(Database &db, valueno slot);
+rust::Vec<uint8_t> database_get_value_upper_bound(Database &db, valueno slot);
+termcount database_get_doclength(Database &db, docid did);
+rust::String database_get_uuid(Database &db);
+uint64_t database_get_revision(Database &db);
+rust::Vec<uint8_t> database_get_metadata(Database &db, rust::Str key);
+std::unique_ptr<TermIterator> database_metadata_keys_begin(Database &db, rust::Str prefix);
+std::unique_ptr<TermIterator> database_spellings_begin(Database &db);
+rust::String database_get_spelling_suggestion(Database &db, rust::Str word, uint32_t max_edit_distance);
+std::unique_ptr<TermIterator> database_synonyms_begin(Database &db, rust::Str term);
+std::unique_ptr<TermIterator> database_synonym_keys_begin(Database &db, rust::Str prefix);
 
 //
 std::unique_ptr<Stem> new_stem(rust::Str lang);
@@ -44,6 +104,23 @@ void commit (WritableDatabase &db);
 void close (WritableDatabase &db);
 docid replace_document(WritableDatabase &db, rust::Str unique_term, Document &doc);
 void delete_document(WritableDatabase &db, rust::Str unique_term);
+docid writable_database_add_document(WritableDatabase &db, Document &doc);
+void writable_database_replace_document_by_docid(WritableDatabase &db, docid did, Document &doc);
+void writable_database_delete_document_by_docid(WritableDatabase &db, docid did);
+void writable_database_begin_transaction(WritableDatabase &db, bool flushed);
+void writable_database_commit_transaction(WritableDatabase &db);
+void writable_database_cancel_transaction(WritableDatabase &db);
+void writable_database_set_metadata(WritableDatabase &db, rust::Str key, rust::Slice<const uint8_t> metadata);
+rust::Vec<uint8_t> writable_database_get_metadata(WritableDatabase &db, rust::Str key);
+std::unique_ptr<TermIterator> writable_database_metadata_keys_begin(WritableDatabase &db, rust::Str prefix);
+void writable_database_add_spelling(WritableDatabase &db, rust::Str word, termcount freqinc);
+void writable_database_remove_spelling(WritableDatabase &db, rust::Str word, termcount freqdec);
+void writable_database_add_synonym(WritableDatabase &db, rust::Str term, rust::Str synonym);
+void writable_database_remove_synonym(WritableDatabase &db, rust::Str term, rust::Str synonym);
+void writable_database_clear_synonyms(WritableDatabase &db, rust::Str term);
+std::unique_ptr<TermIterator> writable_database_spellings_begin(WritableDatabase &db);
+std::unique_ptr<TermIterator> writable_database_synonyms_begin(WritableDatabase &db, rust::Str term);
+std::unique_ptr<TermIterator> writable_database_synonym_keys_begin(WritableDatabase &db, rust::Str prefix);
 const std::string &get_db_description (WritableDatabase &db);
 ulong get_doccount (WritableDatabase &db);
 
@@ -58,6 +135,8 @@ void index_int (TermGenerator &tg, int32_t data, rust::Str prefix);
 void index_long (TermGenerator &tg, int64_t data, rust::Str prefix);
 void index_float(TermGenerator &tg, float in_data, rust::Str prefix);
 void index_double (TermGenerator &tg, double data, rust::Str prefix);
+void term_generator_set_stopper(TermGenerator &tg, std::unique_ptr<Stopper> stopper);
+void term_generator_set_stopper_strategy(TermGenerator &tg, int32_t strategy);
 
 //
 std::unique_ptr<Document> new_document ();
@@ -69,6 +148,25 @@ void add_double(Document &doc, valueno slot, double in_data);
 void set_data (Document &doc, rust::Str data);
 void add_boolean_term(Document &doc, rust::Str data);
 rust::String get_doc_data (Document &doc);
+void document_set_data_bytes(Document &doc, rust::Slice<const uint8_t> data);
+rust::Vec<uint8_t> document_get_data_bytes(Document &doc);
+void document_add_value_bytes(Document &doc, valueno slot, rust::Slice<const uint8_t> value);
+rust::Vec<uint8_t> document_get_value_bytes(Document &doc, valueno slot);
+docid document_get_docid(Document &doc);
+void document_add_term(Document &doc, rust::Str term, termcount wdf_inc);
+void document_add_posting(Document &doc, rust::Str term, termpos pos, termcount wdf_inc);
+void document_remove_term(Document &doc, rust::Str term);
+void document_remove_posting(Document &doc, rust::Str term, termpos pos, termcount wdf_dec);
+void document_clear_terms(Document &doc);
+termcount document_termlist_count(Document &doc);
+std::unique_ptr<TermIterator> document_termlist_begin(Document &doc);
+std::unique_ptr<TermIterator> document_termlist_end(Document &doc);
+rust::String document_get_value(Document &doc, valueno slot);
+void document_remove_value(Document &doc, valueno slot);
+void document_clear_values(Document &doc);
+valueno document_values_count(Document &doc);
+std::unique_ptr<ValueIterator> document_values_begin(Document &doc);
+std::unique_ptr<ValueIterator> document_values_end(Document &doc);
 
 //
 std::unique_ptr<QueryParser> new_query_parser();
@@ -77,37 +175,114 @@ void set_stemmer_to_qp(QueryParser &qp, Stem &stem);
 void set_database(QueryParser &qp, Database &db);
 void add_prefix(QueryParser &qp, rust::Str field, rust::Str prefix);
 void add_boolean_prefix(QueryParser &qp, rust::Str field, rust::Str prefix);
-void add_rangeprocessor(QueryParser &qp, RangeProcessor &range_proc);
-void add_number_rangeprocessor(QueryParser &qp, NumberRangeProcessor &range_proc);
+std::unique_ptr<FieldProcessor> new_rust_field_processor(rust::Box<RustFieldProcessor> processor);
+void query_parser_add_prefix_processor(QueryParser &qp, rust::Str field, std::unique_ptr<FieldProcessor> processor);
+void query_parser_add_boolean_prefix_processor(QueryParser &qp, rust::Str field, std::unique_ptr<FieldProcessor> processor);
 std::unique_ptr<Query> parse_query(QueryParser &qp, rust::Str data, int32_t flags);
 std::unique_ptr<Query> parse_query_with_prefix(QueryParser &qp, rust::Str query, int32_t flags, rust::Str prefix);
+rust::String query_parser_get_corrected_query_string(QueryParser &qp);
+void query_parser_set_stopper(QueryParser &qp, std::unique_ptr<Stopper> stopper);
+std::unique_ptr<TermIterator> query_parser_stoplist_begin(QueryParser &qp);
+std::unique_ptr<TermIterator> query_parser_stoplist_end(QueryParser &qp);
+
+//
+std::unique_ptr<Stopper> new_simple_stopper();
+void simple_stopper_add(Stopper &stopper, rust::Str word);
+std::unique_ptr<Stopper> new_stem_stopper(const Stem &stemmer, int32_t strategy);
+void stem_stopper_add(Stopper &stopper, rust::Str word);
+std::unique_ptr<Stopper> new_rust_stopper(rust::Box<RustStopper> stopper);
+bool stopper_is_stop(const Stopper &stopper, rust::Str word);
 
 //
 std::unique_ptr<Query> new_query();
 std::unique_ptr<Query> new_query_range(int32_t op, valueno slot, double begin, double end);
 std::unique_ptr<Query> new_query_double_with_prefix(rust::Str prefix, double _d);
-std::unique_ptr<Query> add_right_query(Query &this_q, int32_t _op, Query &q);
+std::unique_ptr<Query> add_right_query(const Query &this_q, int32_t _op, const Query &q);
+std::unique_ptr<Query> new_query_term(rust::Str term, termcount wqf, termpos pos);
+std::unique_ptr<Query> new_query_match_all();
+std::unique_ptr<Query> new_query_scale_weight(double factor, const Query &subquery);
+std::unique_ptr<Query> new_query_wildcard(rust::Str pattern, termcount max_expansion, int32_t flags, int32_t combiner);
+std::unique_ptr<Query> new_query_edit_distance(rust::Str pattern, uint32_t edit_distance, termcount max_expansion, int32_t flags, int32_t combiner, size_t min_prefix_len);
+std::unique_ptr<Query> new_query_from_posting_source(rust::Box<RustPostingSource> source);
+std::unique_ptr<Query> new_query_from_builtin_posting_source(std::unique_ptr<PostingSource> source);
+std::unique_ptr<Query> new_query_from_value_map_posting_source(std::unique_ptr<ValueMapPostingSource> source);
+std::unique_ptr<Query> new_query_from_vector(int32_t op, const QueryVector &subqueries, termcount parameter);
+std::unique_ptr<QueryVector> new_query_vector();
+void query_vector_push(QueryVector &subqueries, const Query &q);
+rust::Vec<uint8_t> query_serialise(const Query &q);
+std::unique_ptr<Query> query_unserialise(rust::Slice<const uint8_t> serialised, const Registry &registry);
+int32_t query_get_type(const Query &q);
+size_t query_get_num_subqueries(const Query &q);
+std::unique_ptr<Query> query_get_subquery(const Query &q, size_t n);
+termcount query_get_leaf_wqf(const Query &q);
+termpos query_get_leaf_pos(const Query &q);
+termcount query_get_length(const Query &q);
+std::unique_ptr<TermIterator> query_get_terms_begin(const Query &q);
+std::unique_ptr<TermIterator> query_get_terms_end(const Query &q);
+std::unique_ptr<TermIterator> query_get_unique_terms_begin(const Query &q);
+std::unique_ptr<TermIterator> query_get_unique_terms_end(const Query &q);
 bool query_is_empty (Query &q);
 rust::String get_description (Query &q);
 
+//
+std::unique_ptr<Registry> new_registry();
+
 // Weight
-// BoolWeight
-std::unique_ptr<BoolWeight> new_bool_weight();
-// BM25Weight
-std::unique_ptr<BM25Weight> new_bm25_weight(double k1, double k2, double k3, double b, double min_normlen);
+std::unique_ptr<Weight> new_bool_weight();
+std::unique_ptr<Weight> new_tfidf_weight(rust::Str normalizations);
+std::unique_ptr<Weight> new_bm25_weight(double k1, double k2, double k3, double b, double min_normlen);
+std::unique_ptr<Weight> new_bm25plus_weight(double k1, double k2, double k3, double b, double min_normlen, double delta);
+std::unique_ptr<Weight> new_inl2_weight(double c);
+std::unique_ptr<Weight> new_ifb2_weight(double c);
+std::unique_ptr<Weight> new_ineb2_weight(double c);
+std::unique_ptr<Weight> new_bb2_weight(double c);
+std::unique_ptr<Weight> new_dlh_weight();
+std::unique_ptr<Weight> new_pl2_weight(double c);
+std::unique_ptr<Weight> new_pl2plus_weight(double c, double delta);
+std::unique_ptr<Weight> new_dph_weight();
+std::unique_ptr<Weight> new_lm_weight(double param_log, int32_t smoothing, double smoothing1, double smoothing2);
+std::unique_ptr<Weight> new_coord_weight();
+std::unique_ptr<Weight> new_dice_coeff_weight();
 
 //
 std::unique_ptr <MSet> get_mset(Enquire &en, int32_t from, int32_t size);
 void set_query(Enquire &en, Query &query);
 void set_sort_by_key(Enquire &en, MultiValueKeyMaker &sorter, bool reverse);
+void enquire_set_sort_by_lat_long_distance_key(Enquire &en, LatLongDistanceKeyMaker &sorter, bool reverse);
 void add_matchspy_value_count(Enquire &en, ValueCountMatchSpy &vcms);
-void enquire_set_weighting_scheme_bool(Enquire &en, BoolWeight &weight);
-void enquire_set_weighting_scheme_bm25(Enquire &en, BM25Weight &weight);
+std::unique_ptr<MatchSpy> new_rust_match_spy(rust::Box<RustMatchSpy> spy);
+void enquire_add_matchspy(Enquire &en, MatchSpy &spy);
+void enquire_set_weighting_scheme(Enquire &en, const Weight &weight);
+void enquire_set_weighting_scheme_custom(Enquire &en, rust::Box<RustWeight> weight);
 void enquire_set_docid_order(Enquire &en, int32_t order);
 void enquire_set_sort_by_relevance(Enquire &en);
 void enquire_set_sort_by_value(Enquire &en, valueno sort_key, bool reverse);
 void enquire_set_sort_by_relevance_then_value(Enquire &en, valueno sort_key, bool reverse);
 void enquire_set_collapse_key(Enquire &en, valueno collapse_key, doccount collapse_max);
+std::unique_ptr<MSet> enquire_get_mset_with_rset(Enquire &en, doccount from, doccount size, RSet &rset);
+std::unique_ptr<MSet> enquire_get_mset_with_decider(Enquire &en, doccount from, doccount size, doccount checkatleast, const RustMatchDecider &decider);
+std::unique_ptr<ESet> enquire_get_eset(Enquire &en, termcount maxitems, RSet &rset, int32_t flags, double min_wt);
+std::unique_ptr<ESet> enquire_get_eset_with_decider(Enquire &en, termcount maxitems, RSet &rset, int32_t flags, const RustExpandDecider &decider, double min_wt);
+
+//
+std::unique_ptr<RSet> new_rset();
+void rset_add_document(RSet &rset, docid did);
+void rset_remove_document(RSet &rset, docid did);
+bool rset_contains(RSet &rset, docid did);
+doccount rset_size(RSet &rset);
+bool rset_empty(RSet &rset);
+
+//
+termcount eset_size(ESet &set);
+termcount eset_get_ebound(ESet &set);
+std::unique_ptr<ESet> eset_clone(const ESet &set);
+std::unique_ptr<ESetIterator> eset_begin(ESet &set);
+std::unique_ptr<ESetIterator> eset_end(ESet &set);
+std::unique_ptr<ESetIterator> eset_iterator_at(ESet &set, termcount index);
+rust::String eset_iterator_get_term(ESetIterator &iter);
+double eset_iterator_get_weight(ESetIterator &iter);
+bool eset_iterator_eq(ESetIterator &iter, ESetIterator &other);
+void eset_iterator_next(ESetIterator &iter);
 
 //
 int get_matches_estimated (MSet &set);
@@ -116,11 +291,19 @@ rust::String mset_snippet(MSet &set, rust::Str text, int32_t length, Stem &stem,
 std::unique_ptr<MSetIterator> mset_begin (MSet &set);
 std::unique_ptr<MSetIterator> mset_end (MSet &set);
 std::unique_ptr<MSetIterator> mset_back (MSet &set);
+std::unique_ptr<MSetIterator> mset_iterator_at (MSet &set, doccount index);
+std::unique_ptr<MSet> mset_clone (const MSet &set);
 
 //
 std::unique_ptr<Document> mset_iterator_get_document(MSetIterator &iter);
 bool mset_iterator_eq(MSetIterator &iter, MSetIterator &other);
 void mset_iterator_next (MSetIterator &iter);
+docid mset_iterator_get_docid(MSetIterator &iter);
+doccount mset_iterator_get_rank(MSetIterator &iter);
+double mset_iterator_get_weight(MSetIterator &iter);
+int32_t mset_iterator_get_percent(MSetIterator &iter);
+doccount mset_iterator_get_collapse_count(MSetIterator &iter);
+rust::Vec<uint8_t> mset_iterator_get_collapse_key(MSetIterator &iter);
 
 //
 std::unique_ptr<MultiValueKeyMaker> new_multi_value_key_maker ();
@@ -128,16 +311,49 @@ void add_value_to_multi_value_key_maker(MultiValueKeyMaker &this_m, valueno slot
 
 std::unique_ptr<ValueCountMatchSpy> new_value_count_match_spy (valueno slot);
 
-std::unique_ptr<RangeProcessor> new_range_processor (valueno slot, rust::Str str, int32_t flags);
-std::unique_ptr<NumberRangeProcessor> new_number_range_processor (valueno slot, rust::Str prefix, int32_t flags);
+std::unique_ptr<PostingSource> new_value_weight_posting_source(valueno slot);
+std::unique_ptr<PostingSource> new_decreasing_value_weight_posting_source(valueno slot, docid range_start, docid range_end);
+std::unique_ptr<PostingSource> new_fixed_weight_posting_source(double wt);
+std::unique_ptr<ValueMapPostingSource> new_value_map_posting_source(valueno slot);
+std::unique_ptr<PostingSource> new_lat_long_distance_posting_source(valueno slot, const LatLongCoords &centre, const GreatCircleMetric &metric, double max_range, double k1, double k2);
+void value_map_posting_source_add_mapping(ValueMapPostingSource &source, rust::Str key, double wt);
+void value_map_posting_source_clear_mappings(ValueMapPostingSource &source);
+void value_map_posting_source_set_default_weight(ValueMapPostingSource &source, double wt);
+
+std::unique_ptr<RangeProcessor> new_rust_range_processor(rust::Str prefix, int32_t flags, rust::Box<RustRangeProcessor> processor);
+void query_parser_add_rangeprocessor(QueryParser &qp, std::unique_ptr<RangeProcessor> range_proc, bool has_grouping, rust::Str grouping);
+std::unique_ptr<RangeProcessor> new_number_range_processor (valueno slot, rust::Str prefix, int32_t flags);
+std::unique_ptr<RangeProcessor> new_date_range_processor (valueno slot, rust::Str prefix, int32_t flags, int32_t epoch_year);
+std::unique_ptr<RangeProcessor> new_unit_range_processor (valueno slot, rust::Str prefix);
 
 //
 std::unique_ptr<TermIterator> value_count_matchspy_values_begin(ValueCountMatchSpy &vcms);
 std::unique_ptr<TermIterator> value_count_matchspy_values_end(ValueCountMatchSpy &vcms);
 int value_count_matchspy_get_total(ValueCountMatchSpy &vcms);
 
+//
+std::unique_ptr<LatLongCoords> new_lat_long_coords();
+void lat_long_coords_append(LatLongCoords &coords, double latitude, double longitude);
+size_t lat_long_coords_size(const LatLongCoords &coords);
+rust::Vec<double> lat_long_coords_flatten(const LatLongCoords &coords);
+rust::Vec<uint8_t> lat_long_coords_serialise(const LatLongCoords &coords);
+void lat_long_coords_unserialise(LatLongCoords &coords, rust::Slice<const uint8_t> data);
+std::unique_ptr<GreatCircleMetric> new_great_circle_metric();
+std::unique_ptr<GreatCircleMetric> new_great_circle_metric_with_radius(double radius);
+double great_circle_metric_distance(const GreatCircleMetric &metric, const LatLongCoords &a, const LatLongCoords &b);
+std::unique_ptr<LatLongDistanceKeyMaker> new_lat_long_distance_key_maker(valueno slot, const LatLongCoords &centre, const GreatCircleMetric &metric, bool has_defdistance, double defdistance);
+
 //
 rust::String term_iterator_get_termfreq_value(TermIterator &titer);
 int term_iterator_get_termfreq_freq(TermIterator &titer);
 bool term_iterator_eq(TermIterator &titer, TermIterator &other);
-void term_iterator_next(TermIterator &titer);
\ No newline at end of file
+std::unique_ptr<TermIterator> new_term_iterator_end();
+rust::String term_iterator_get_term(TermIterator &titer);
+termcount term_iterator_get_wdf(TermIterator &titer);
+void term_iterator_next(TermIterator &titer);
+
+//
+rust::Vec<uint8_t> value_iterator_get_value(ValueIterator &viter);
+valueno value_iterator_get_valueno(ValueIterator &viter);
+bool value_iterator_eq(ValueIterator &viter, ValueIterator &other);
+void value_iterator_next(ValueIterator &viter);
\ No newline at end of file